@@ -1,10 +1,8 @@
 use criterion::{criterion_group, criterion_main, Criterion};
 
-#[macro_use]
-extern crate ff;
-use ff::*;
-
-use poseidon_rs::{Fr, Poseidon};
+use poseidon_rs::fr::*;
+use poseidon_rs::merkle_tree::MerkleTree;
+use poseidon_rs::{FieldBackend, Fr, Poseidon};
 
 fn criterion_benchmark(c: &mut Criterion) {
     let b1: Fr = Fr::from_str(
@@ -23,6 +21,37 @@ fn criterion_benchmark(c: &mut Criterion) {
     c.bench_function("hash", |b| {
         b.iter(|| poseidon.hash(big_arr.clone()).unwrap())
     });
+
+    let fixed = poseidon_rs::fixed::Poseidon::<3>::new().unwrap();
+    c.bench_function("hash fixed width", |b| {
+        b.iter(|| fixed.hash(&big_arr).unwrap())
+    });
+
+    // the same permutation with each field multiplication, the asm one
+    // only with the asm feature on a supported CPU
+    let mut group = c.benchmark_group("field backend");
+    for backend in [FieldBackend::Ff, FieldBackend::Asm] {
+        if let Ok(fixed) = fixed.with_backend(backend) {
+            group.bench_function(format!("{:?}", backend), |b| {
+                b.iter(|| fixed.hash(&big_arr).unwrap())
+            });
+        }
+    }
+    group.finish();
+
+    let mut hasher = poseidon_rs::hasher::PoseidonHasher::new(&poseidon, 2).unwrap();
+    c.bench_function("hash reusable hasher", |b| {
+        b.iter(|| hasher.hash(&big_arr).unwrap())
+    });
+
+    let leaves = vec![b1; 1 << 10];
+    c.bench_function("merkle tree 2^10 leaves", |b| {
+        b.iter(|| {
+            MerkleTree::from_leaves(&poseidon, 10, &leaves)
+                .unwrap()
+                .root()
+        })
+    });
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -0,0 +1,68 @@
+// Converts the decimal round constants and MDS matrices of src/constants.rs
+// to field elements in Montgomery form (R = 2^256, as used by Fr), written as
+// static tables that fixed::Poseidon borrows instead of parsing and copying
+// them on each instantiation. The matrices are flattened row by row.
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+#[allow(dead_code)]
+mod constants {
+    include!("src/constants.rs");
+}
+
+include!("src/mont.rs");
+
+fn to_montgomery(dec: &str) -> [u64; 4] {
+    let mut r = [0u64; 4];
+    for d in dec.bytes() {
+        assert!(d.is_ascii_digit(), "Invalid constant {}", dec);
+        let mut carry = (d - b'0') as u128;
+        for limb in r.iter_mut() {
+            let v = *limb as u128 * 10 + carry;
+            *limb = v as u64;
+            carry = v >> 64;
+        }
+        assert_eq!(carry, 0, "Constant out of range {}", dec);
+    }
+    assert!(lt_modulus(&r), "Constant out of range {}", dec);
+    mont_mul(&r, &R2)
+}
+
+fn table<'a>(out: &mut String, name: &str, widths: impl Iterator<Item = Vec<&'a str>>) {
+    let widths: Vec<Vec<&str>> = widths.collect();
+    writeln!(
+        out,
+        "pub(crate) static {}: [&[Fr]; {}] = [",
+        name,
+        widths.len()
+    )
+    .unwrap();
+    for width in widths {
+        out.push_str("    &[\n");
+        for e in width {
+            let l = to_montgomery(e);
+            writeln!(
+                out,
+                "        Fr(FrRepr([{:#018x}, {:#018x}, {:#018x}, {:#018x}])),",
+                l[0], l[1], l[2], l[3]
+            )
+            .unwrap();
+        }
+        out.push_str("    ],\n");
+    }
+    out.push_str("];\n");
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=src/constants.rs");
+    println!("cargo:rerun-if-changed=src/mont.rs");
+
+    let mut out = String::new();
+    table(&mut out, "C", constants::C_STR.iter().map(|c| c.to_vec()));
+    table(&mut out, "M", constants::M_STR.iter().map(|m| m.concat()));
+    let dest = Path::new(&env::var("OUT_DIR").unwrap()).join("tables.rs");
+    fs::write(dest, out).unwrap();
+}
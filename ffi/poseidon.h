@@ -0,0 +1,69 @@
+#ifndef POSEIDON_H
+#define POSEIDON_H
+
+/* Generated with cbindgen from src/ffi.rs, do not edit by hand. */
+
+#include <stddef.h>
+#include <stdint.h>
+
+typedef enum PoseidonStatus {
+  POSEIDON_STATUS_OK = 0,
+  POSEIDON_STATUS_NULL_POINTER = 1,
+  POSEIDON_STATUS_INVALID_LENGTH = 2,
+  POSEIDON_STATUS_EMPTY_INPUT = 3,
+  POSEIDON_STATUS_INPUT_TOO_LONG = 4,
+  POSEIDON_STATUS_NON_CANONICAL = 5,
+  POSEIDON_STATUS_UNSUPPORTED_FRAME_SIZE = 6,
+  POSEIDON_STATUS_ERROR = 255,
+} PoseidonStatus;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Hashes `inputs_len / 32` field elements.
+ *
+ * # Safety
+ * `inputs` must point to `inputs_len` readable bytes and `out` to 32
+ * writable bytes.
+ */
+enum PoseidonStatus poseidon_hash(const uint8_t *inputs, size_t inputs_len, uint8_t *out);
+
+/**
+ * Hashes a message of any length, as `HashBytesX` from go-iden3-crypto.
+ *
+ * # Safety
+ * `msg` must point to `msg_len` readable bytes and `out` to 32 writable
+ * bytes.
+ */
+enum PoseidonStatus poseidon_hash_bytes(const uint8_t *msg,
+                                        size_t msg_len,
+                                        size_t frame_size,
+                                        uint8_t *out);
+
+/**
+ * Hashes any number of field elements, as `SpongeHashX` from
+ * go-iden3-crypto.
+ *
+ * # Safety
+ * `inputs` must point to `inputs_len` readable bytes and `out` to 32
+ * writable bytes.
+ */
+enum PoseidonStatus poseidon_sponge_hash(const uint8_t *inputs,
+                                         size_t inputs_len,
+                                         size_t frame_size,
+                                         uint8_t *out);
+
+/**
+ * Static, nul terminated description of a status. Takes the status as an
+ * integer, so that any value is accepted, and describes the ones out of
+ * range as an unknown status.
+ */
+const char *poseidon_status_message(uint32_t status);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* POSEIDON_H */
@@ -0,0 +1,52 @@
+#include <assert.h>
+#include <stdio.h>
+#include <string.h>
+
+#include "poseidon.h"
+
+/* poseidon([1, 2]), little-endian */
+static const uint8_t HASH_1_2[32] = {
+    0x9a, 0x18, 0x17, 0x44, 0x7a, 0x60, 0x19, 0x9e, 0x51, 0x45, 0x32,
+    0x74, 0xf2, 0x17, 0x36, 0x2a, 0xcf, 0xe9, 0x62, 0x96, 0x6b, 0x4c,
+    0xf6, 0x3d, 0x41, 0x90, 0xd6, 0xe7, 0xf5, 0xc0, 0x5c, 0x11,
+};
+
+int main(void) {
+    uint8_t inputs[64] = {0};
+    uint8_t out[32];
+    inputs[0] = 1;
+    inputs[32] = 2;
+
+    assert(poseidon_hash(inputs, sizeof(inputs), out) == POSEIDON_STATUS_OK);
+    assert(memcmp(out, HASH_1_2, 32) == 0);
+
+    /* one element sponge hashed in a frame of 2 is poseidon([x, 0]) */
+    uint8_t padded[64] = {0};
+    uint8_t sponge[32];
+    padded[0] = 1;
+    assert(poseidon_sponge_hash(inputs, 32, 2, sponge) == POSEIDON_STATUS_OK);
+    assert(poseidon_hash(padded, sizeof(padded), out) == POSEIDON_STATUS_OK);
+    assert(memcmp(out, sponge, 32) == 0);
+
+    const char *msg = "Lorem ipsum dolor sit amet";
+    assert(poseidon_hash_bytes((const uint8_t *)msg, strlen(msg), 16, out) ==
+           POSEIDON_STATUS_OK);
+
+    assert(poseidon_hash(inputs, 63, out) == POSEIDON_STATUS_INVALID_LENGTH);
+    assert(poseidon_hash(NULL, 0, out) == POSEIDON_STATUS_EMPTY_INPUT);
+    assert(poseidon_hash(NULL, 32, out) == POSEIDON_STATUS_NULL_POINTER);
+    assert(poseidon_hash(inputs, 64, NULL) == POSEIDON_STATUS_NULL_POINTER);
+    memset(inputs, 0xff, 32);
+    assert(poseidon_hash(inputs, 32, out) == POSEIDON_STATUS_NON_CANONICAL);
+    uint8_t too_long[17 * 32] = {0};
+    assert(poseidon_hash(too_long, sizeof(too_long), out) ==
+           POSEIDON_STATUS_INPUT_TOO_LONG);
+    assert(poseidon_sponge_hash(too_long, 32, 1, out) ==
+           POSEIDON_STATUS_UNSUPPORTED_FRAME_SIZE);
+    assert(strcmp(poseidon_status_message(POSEIDON_STATUS_NON_CANONICAL),
+                  "Non canonical field element") == 0);
+    assert(strcmp(poseidon_status_message(1000), "Unknown status") == 0);
+
+    printf("ok\n");
+    return 0;
+}
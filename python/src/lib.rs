@@ -0,0 +1,181 @@
+// Python bindings. Field elements are Python ints, which must be canonical
+// (smaller than the modulus), and errors are raised as ValueError.
+use std::convert::TryFrom;
+use std::sync::OnceLock;
+
+use num_bigint::BigUint;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use poseidon_rs::{merkle_tree, Fr, PoseidonError};
+
+static POSEIDON: OnceLock<poseidon_rs::Poseidon> = OnceLock::new();
+
+fn poseidon() -> &'static poseidon_rs::Poseidon {
+    POSEIDON.get_or_init(poseidon_rs::Poseidon::new)
+}
+
+fn py_error(e: PoseidonError) -> PyErr {
+    PyValueError::new_err(e.to_string())
+}
+
+fn to_fr(n: &BigUint) -> PyResult<Fr> {
+    Fr::try_from(n).map_err(|e| py_error(e.into()))
+}
+
+fn to_frs(v: &[BigUint]) -> PyResult<Vec<Fr>> {
+    v.iter().map(to_fr).collect()
+}
+
+fn to_ints(v: &[Fr]) -> Vec<BigUint> {
+    v.iter().map(BigUint::from).collect()
+}
+
+#[pyclass(frozen)]
+struct Poseidon;
+
+#[pymethods]
+impl Poseidon {
+    #[new]
+    fn new() -> Self {
+        Poseidon
+    }
+
+    fn hash(&self, inputs: Vec<BigUint>) -> PyResult<BigUint> {
+        let h = poseidon().hash(to_frs(&inputs)?).map_err(py_error)?;
+        Ok(h.into())
+    }
+
+    fn hash_with_state(&self, inputs: Vec<BigUint>, init_state: BigUint) -> PyResult<BigUint> {
+        let h = poseidon()
+            .hash_with_state(to_frs(&inputs)?, to_fr(&init_state)?)
+            .map_err(py_error)?;
+        Ok(h.into())
+    }
+
+    // the full state after the permutation of width len(state)
+    fn permutation(&self, state: Vec<BigUint>) -> PyResult<Vec<BigUint>> {
+        let mut state = to_frs(&state)?;
+        poseidon().permutation(&mut state).map_err(py_error)?;
+        Ok(to_ints(&state))
+    }
+
+    #[pyo3(signature = (data, frame_size=16))]
+    fn hash_bytes(&self, data: &[u8], frame_size: usize) -> PyResult<BigUint> {
+        let h = poseidon()
+            .hash_bytes_x(data, frame_size)
+            .map_err(py_error)?;
+        Ok(h.into())
+    }
+
+    #[pyo3(signature = (inputs, frame_size=16))]
+    fn sponge_hash(&self, inputs: Vec<BigUint>, frame_size: usize) -> PyResult<BigUint> {
+        let h = poseidon()
+            .sponge_hash(&to_frs(&inputs)?, frame_size)
+            .map_err(py_error)?;
+        Ok(h.into())
+    }
+}
+
+#[pyclass(frozen)]
+struct MerkleProof {
+    inner: merkle_tree::MerkleProof,
+}
+
+#[pymethods]
+impl MerkleProof {
+    #[new]
+    fn new(index: usize, siblings: Vec<BigUint>) -> PyResult<Self> {
+        Ok(MerkleProof {
+            inner: merkle_tree::MerkleProof {
+                index,
+                siblings: to_frs(&siblings)?,
+            },
+        })
+    }
+
+    #[getter]
+    fn index(&self) -> usize {
+        self.inner.index
+    }
+
+    #[getter]
+    fn siblings(&self) -> Vec<BigUint> {
+        to_ints(&self.inner.siblings)
+    }
+
+    fn compute_root(&self, leaf: BigUint) -> PyResult<BigUint> {
+        Ok(self.inner.compute_root(poseidon(), &to_fr(&leaf)?).into())
+    }
+
+    fn verify(&self, root: BigUint, leaf: BigUint) -> PyResult<bool> {
+        Ok(self
+            .inner
+            .verify(poseidon(), &to_fr(&root)?, &to_fr(&leaf)?))
+    }
+}
+
+#[pyclass]
+struct MerkleTree {
+    inner: merkle_tree::MerkleTree<'static>,
+}
+
+#[pymethods]
+impl MerkleTree {
+    #[new]
+    #[pyo3(signature = (depth, leaves=Vec::new()))]
+    fn new(depth: usize, leaves: Vec<BigUint>) -> PyResult<Self> {
+        let inner = merkle_tree::MerkleTree::from_leaves(poseidon(), depth, &to_frs(&leaves)?)
+            .map_err(py_error)?;
+        Ok(MerkleTree { inner })
+    }
+
+    #[getter]
+    fn depth(&self) -> usize {
+        self.inner.depth()
+    }
+
+    #[getter]
+    fn root(&self) -> BigUint {
+        self.inner.root().into()
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn leaf(&self, index: usize) -> PyResult<BigUint> {
+        if index >= self.inner.capacity() {
+            return Err(py_error(PoseidonError::IndexOutOfRange(index)));
+        }
+        Ok(self.inner.leaf(index).into())
+    }
+
+    fn push(&mut self, leaf: BigUint) -> PyResult<usize> {
+        self.inner.push(to_fr(&leaf)?).map_err(py_error)
+    }
+
+    fn set(&mut self, index: usize, leaf: BigUint) -> PyResult<()> {
+        self.inner.set(index, to_fr(&leaf)?).map_err(py_error)
+    }
+
+    fn proof(&self, index: usize) -> PyResult<MerkleProof> {
+        let inner = self.inner.proof(index).map_err(py_error)?;
+        Ok(MerkleProof { inner })
+    }
+}
+
+#[pyfunction]
+fn hash_node(left: BigUint, right: BigUint) -> PyResult<BigUint> {
+    Ok(merkle_tree::hash_node(poseidon(), &to_fr(&left)?, &to_fr(&right)?).into())
+}
+
+#[pymodule]
+#[pyo3(name = "poseidon")]
+fn poseidon_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Poseidon>()?;
+    m.add_class::<MerkleTree>()?;
+    m.add_class::<MerkleProof>()?;
+    m.add_function(wrap_pyfunction!(hash_node, m)?)?;
+    Ok(())
+}
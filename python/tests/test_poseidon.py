@@ -0,0 +1,100 @@
+import pytest
+
+from poseidon import MerkleProof, MerkleTree, Poseidon, hash_node
+
+MODULUS = 21888242871839275222246405745257275088548364400416034343698204186575808495617
+
+# same vectors as test_hash in src/lib.rs
+VECTORS = [
+    ([1], 18586133768512220936620570745912940619677854269274689475585506675881198879027),
+    ([1, 2], 7853200120776062878684798364095072458815029376092732009249414926327459813530),
+    ([1, 2, 0, 0, 0], 1018317224307729531995786483840663576608797660851238720571059489595066344487),
+    ([1, 2, 0, 0, 0, 0], 15336558801450556532856248569924170992202208561737609669134139141992924267169),
+    ([3, 4, 0, 0, 0], 5811595552068139067952687508729883632420015185677766880877743348592482390548),
+    ([3, 4, 0, 0, 0, 0], 12263118664590987767234828103155242843640892839966517009184493198782366909018),
+    ([1, 2, 3, 4, 5, 6], 20400040500897583745843009878988256314335038853985262692600694741116813247201),
+    (list(range(1, 15)), 8354478399926161176778659061636406690034081872658507739535256090879947077494),
+    ([1, 2, 3, 4, 5, 6, 7, 8, 9] + [0] * 5, 5540388656744764564518487011617040650780060800286365721923524861648744699539),
+    ([1, 2, 3, 4, 5, 6, 7, 8, 9] + [0] * 7, 11882816200654282475720830292386643970958445617880627439994635298904836126497),
+    (list(range(1, 17)), 9989051620750914585850546081941653841776809718687451684622678807385399211877),
+]
+
+
+@pytest.fixture
+def poseidon():
+    return Poseidon()
+
+
+@pytest.mark.parametrize("inputs,expected", VECTORS)
+def test_hash(poseidon, inputs, expected):
+    assert poseidon.hash(inputs) == expected
+
+
+def test_hash_errors(poseidon):
+    with pytest.raises(ValueError):
+        poseidon.hash([])
+    with pytest.raises(ValueError):
+        poseidon.hash(list(range(17)))
+    with pytest.raises(ValueError):
+        poseidon.hash([MODULUS])
+    with pytest.raises(OverflowError):
+        poseidon.hash([-1])
+
+
+def test_permutation(poseidon):
+    state = poseidon.permutation([0, 1, 2])
+    assert len(state) == 3
+    assert state[0] == poseidon.hash([1, 2])
+    assert poseidon.permutation([5, 1, 2])[0] == poseidon.hash_with_state([1, 2], 5)
+    with pytest.raises(ValueError):
+        poseidon.permutation([1])
+
+
+def test_sponge(poseidon):
+    # one chunk of 31 bytes hashed in a frame of 16 elements
+    h = poseidon.hash_bytes(b"\x01")
+    assert h == poseidon.hash([1 << 240] + [0] * 15)
+    assert poseidon.hash_bytes(b"\x01", 2) == poseidon.hash([1 << 240, 0])
+    # checked against go-iden3-crypto by the go job of CI
+    assert poseidon.sponge_hash(list(range(1, 34))) == (
+        14100754985345369187053942966124810217803624427243640962979300983969861343646
+    )
+    # light-poseidon 0.2, chaining the frames as SpongeHashX, see sponge.rs
+    assert poseidon.sponge_hash(list(range(1, 6)), 3) == (
+        2471372894682600937379875366137951766263546689296527096431777319482417487166
+    )
+    assert poseidon.sponge_hash(list(range(1, 34)), 12) == (
+        1761314100012910163355364640892676848271037958674929011674953971240823694381
+    )
+    with pytest.raises(ValueError):
+        poseidon.sponge_hash([1], 1)
+
+
+def test_merkle_tree():
+    leaves = [1, 2, 3]
+    tree = MerkleTree(2, leaves)
+    assert len(tree) == 3
+    assert tree.root == hash_node(hash_node(1, 2), hash_node(3, 0))
+
+    incremental = MerkleTree(2)
+    for leaf in leaves:
+        incremental.push(leaf)
+    assert incremental.root == tree.root
+    incremental.push(4)
+    with pytest.raises(ValueError):
+        incremental.push(5)
+
+    for i, leaf in enumerate(leaves):
+        proof = tree.proof(i)
+        assert proof.index == i
+        assert len(proof.siblings) == 2
+        assert proof.verify(tree.root, leaf)
+        assert not proof.verify(tree.root, leaf + 1)
+        copy = MerkleProof(proof.index, proof.siblings)
+        assert copy.compute_root(leaf) == tree.root
+
+    tree.set(3, 7)
+    assert tree.leaf(3) == 7
+    assert tree.proof(3).verify(tree.root, 7)
+    with pytest.raises(ValueError):
+        tree.set(4, 1)
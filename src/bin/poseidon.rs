@@ -0,0 +1,379 @@
+// Command line tool to compute hashes, permutation traces, constants and
+// known-answer vectors.
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io::{self, Read};
+use std::process;
+
+use serde::Serialize;
+
+#[cfg(feature = "codegen")]
+use poseidon_rs::codegen;
+use poseidon_rs::convert::{Dec, Hex};
+use poseidon_rs::fr::*;
+use poseidon_rs::r1cs::wtns_bytes;
+use poseidon_rs::{load_constants, Fr, Poseidon, PoseidonError};
+
+const USAGE: &str = "Usage: poseidon <command> [options] [args]
+
+Commands:
+  hash <x>...            hash field elements, given in decimal or 0x hex
+  sponge <x>...          sponge hash any number of field elements
+  hash-bytes <msg>       hash a message, given as text, or read from stdin if
+                         it is -
+  trace <x>...           print the state after each step of each round
+  constants <t>          print the constants for width t as JSON
+  vectors                print known-answer vectors for every width as JSON
+  r1cs <n> <file>        write the circuit of hash with n inputs as circom .r1cs
+  wtns <file> <x>...     write the witness of that circuit for the inputs as
+                         circom .wtns
+  circom <n>             print the circom template of hash with n inputs (with
+                         the codegen feature)
+
+Options:
+  --hex                  print hashes in hex instead of decimal
+  --frame-size <n>       frame size of sponge and hash-bytes (default 16)
+  --hex-input            read the message of hash-bytes as hex bytes, with or
+                         without 0x";
+
+struct Args {
+    hex: bool,
+    hex_input: bool,
+    frame_size: usize,
+    positional: Vec<String>,
+}
+
+fn parse_args(args: &[String]) -> Result<Args, Box<dyn Error>> {
+    let mut parsed = Args {
+        hex: false,
+        hex_input: false,
+        frame_size: 16,
+        positional: Vec::new(),
+    };
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--hex" => parsed.hex = true,
+            "--hex-input" => parsed.hex_input = true,
+            "--frame-size" => {
+                let n = it.next().ok_or("Missing value of --frame-size")?;
+                parsed.frame_size = n.parse()?;
+            }
+            a if a.starts_with("--") => return Err(format!("Unknown option {}", a).into()),
+            _ => parsed.positional.push(arg.clone()),
+        }
+    }
+    Ok(parsed)
+}
+
+fn parse_fr(s: &str) -> Result<Fr, Box<dyn Error>> {
+    let r = if s.starts_with("0x") {
+        Fr::parse_hex(s)
+    } else {
+        Fr::parse_dec(s)
+    };
+    r.map_err(|e| format!("{}: {}", s, e).into())
+}
+
+fn parse_frs(args: &[String]) -> Result<Vec<Fr>, Box<dyn Error>> {
+    args.iter().map(|s| parse_fr(s)).collect()
+}
+
+fn parse_hex_bytes(s: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let s = s.strip_prefix("0x").unwrap_or(s);
+    if !s.is_ascii() || !s.len().is_multiple_of(2) {
+        return Err("Invalid hex bytes".into());
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| Ok(u8::from_str_radix(&s[i..i + 2], 16)?))
+        .collect()
+}
+
+fn format_fr(e: &Fr, hex: bool) -> String {
+    if hex {
+        Hex(*e).to_string()
+    } else {
+        Dec(*e).to_string()
+    }
+}
+
+fn trace(inp: &[Fr]) -> Result<String, Box<dyn Error>> {
+    Ok(Poseidon::new().hash_trace(inp)?.to_string())
+}
+
+#[derive(Serialize)]
+struct WidthConstants {
+    t: usize,
+    n_rounds_f: usize,
+    n_rounds_p: usize,
+    #[serde(rename = "C")]
+    c: Vec<Hex>,
+    #[serde(rename = "M")]
+    m: Vec<Vec<Hex>>,
+}
+
+fn width_constants(t: usize) -> Result<String, Box<dyn Error>> {
+    let constants = load_constants()?;
+    if t < 2 || t > constants.n_rounds_p.len() + 1 {
+        return Err(PoseidonError::UnsupportedWidth(t).into());
+    }
+    let hex = |v: &Vec<Fr>| v.iter().map(|e| Hex(*e)).collect();
+    let w = WidthConstants {
+        t,
+        n_rounds_f: constants.n_rounds_f,
+        n_rounds_p: constants.n_rounds_p[t - 2],
+        c: hex(&constants.c[t - 2]),
+        m: constants.m[t - 2].iter().map(hex).collect(),
+    };
+    Ok(serde_json::to_string_pretty(&w)? + "\n")
+}
+
+#[derive(Serialize)]
+struct Vector {
+    inputs: Vec<Fr>,
+    output: Fr,
+}
+
+// for each number of inputs: 1..n, all zeros and all p - 1
+fn vectors() -> Result<String, Box<dyn Error>> {
+    let poseidon = Poseidon::new();
+    let mut p_minus_1 = Fr::one();
+    p_minus_1.negate();
+    let mut v = Vec::new();
+    for n in 1..=16 {
+        let cases = vec![
+            (1..=n as u64).map(Fr::from_u64).collect(),
+            vec![Fr::zero(); n],
+            vec![p_minus_1; n],
+        ];
+        for inputs in cases {
+            let output = poseidon.hash(inputs.clone())?;
+            v.push(Vector { inputs, output });
+        }
+    }
+    Ok(serde_json::to_string_pretty(&v)? + "\n")
+}
+
+fn run(args: &[String]) -> Result<String, Box<dyn Error>> {
+    let (command, rest) = args.split_first().ok_or("Missing command")?;
+    let args = parse_args(rest)?;
+    let p = &args.positional;
+    match command.as_str() {
+        "hash" => {
+            let h = Poseidon::new().hash(parse_frs(p)?)?;
+            Ok(format_fr(&h, args.hex) + "\n")
+        }
+        "sponge" => {
+            let h = Poseidon::new().sponge_hash(&parse_frs(p)?, args.frame_size)?;
+            Ok(format_fr(&h, args.hex) + "\n")
+        }
+        "hash-bytes" => {
+            let mut msg = match p.as_slice() {
+                [m] if m == "-" => {
+                    let mut b = Vec::new();
+                    io::stdin().read_to_end(&mut b)?;
+                    b
+                }
+                [m] => m.as_bytes().to_vec(),
+                _ => return Err("Expected one message".into()),
+            };
+            if args.hex_input {
+                msg = parse_hex_bytes(String::from_utf8(msg)?.trim())?;
+            }
+            let h = Poseidon::new().hash_bytes_x(&msg, args.frame_size)?;
+            Ok(format_fr(&h, args.hex) + "\n")
+        }
+        "trace" => trace(&parse_frs(p)?),
+        "constants" => match p.as_slice() {
+            [t] => width_constants(t.parse()?),
+            _ => Err("Expected the width".into()),
+        },
+        "vectors" => vectors(),
+        "r1cs" => match p.as_slice() {
+            [n, file] => {
+                fs::write(file, Poseidon::new().r1cs(n.parse()?)?.to_bytes())?;
+                Ok(String::new())
+            }
+            _ => Err("Expected the number of inputs and the file".into()),
+        },
+        "wtns" => match p.split_first() {
+            Some((file, inp)) => {
+                let witness = Poseidon::new().witness(&parse_frs(inp)?)?;
+                fs::write(file, wtns_bytes(&witness))?;
+                Ok(String::new())
+            }
+            None => Err("Expected the file".into()),
+        },
+        #[cfg(feature = "codegen")]
+        "circom" => match p.as_slice() {
+            [n] => Ok(codegen::circom_template(&load_constants()?, n.parse()?)?),
+            _ => Err("Expected the number of inputs".into()),
+        },
+        "help" | "--help" | "-h" => Ok(format!("{}\n", USAGE)),
+        _ => Err(format!("Unknown command {}", command).into()),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match run(&args) {
+        Ok(out) => print!("{}", out),
+        Err(e) => {
+            eprintln!("Error: {}\n\n{}", e, USAGE);
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    const H_1_2: &str =
+        "7853200120776062878684798364095072458815029376092732009249414926327459813530\n";
+
+    fn run_args(args: &[&str]) -> Result<String, Box<dyn Error>> {
+        run(&args.iter().map(|a| a.to_string()).collect::<Vec<_>>())
+    }
+
+    // file in the temp dir, unique to the test and the process, removed
+    // when dropped
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> TempFile {
+            TempFile(env::temp_dir().join(format!("poseidon_cli_{}_{}", process::id(), name)))
+        }
+
+        fn path(&self) -> &str {
+            self.0.to_str().unwrap()
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_hash() {
+        assert_eq!(run_args(&["hash", "1", "2"]).unwrap(), H_1_2);
+        assert_eq!(run_args(&["hash", "0x01", "2"]).unwrap(), H_1_2);
+        assert_eq!(
+            run_args(&["hash", "--hex", "1", "2"]).unwrap(),
+            "0x115cc0f5e7d690413df64c6b9662e9cf2a3617f2743245519e19607a4417189a\n"
+        );
+        assert!(run_args(&["hash"]).is_err());
+        assert!(run_args(&["hash", "01"]).is_err());
+        assert!(run_args(&["hash", "--foo", "1"]).is_err());
+    }
+
+    #[test]
+    fn test_sponge() {
+        // a single frame, zero padded, is the hash of the frame
+        let mut args = vec!["hash", "1", "2"];
+        args.extend(["0"; 14]);
+        assert_eq!(
+            run_args(&["sponge", "1", "2"]).unwrap(),
+            run_args(&args).unwrap()
+        );
+        assert_eq!(
+            run_args(&["sponge", "--frame-size", "2", "1", "2"]).unwrap(),
+            H_1_2
+        );
+        assert!(run_args(&["sponge", "--frame-size", "1", "1"]).is_err());
+        assert!(run_args(&["sponge"]).is_err());
+    }
+
+    #[test]
+    fn test_hash_bytes() {
+        let abc = run_args(&["hash-bytes", "abc"]).unwrap();
+        assert_eq!(
+            run_args(&["hash-bytes", "--hex-input", "0x616263"]).unwrap(),
+            abc
+        );
+        assert_eq!(
+            run_args(&["hash-bytes", "--hex-input", "616263"]).unwrap(),
+            abc
+        );
+        // without --hex-input, 0x is text
+        assert_ne!(run_args(&["hash-bytes", "0x616263"]).unwrap(), abc);
+        assert!(run_args(&["hash-bytes", "--hex-input", "abc"]).is_err());
+        assert_ne!(
+            run_args(&["hash-bytes", "--frame-size", "2", "abc"]).unwrap(),
+            run_args(&["hash-bytes", "abc"]).unwrap()
+        );
+        assert!(run_args(&["hash-bytes"]).is_err());
+    }
+
+    #[test]
+    fn test_trace() {
+        let trace = run_args(&["trace", "1", "2"]).unwrap();
+        assert_eq!(trace.lines().count(), 3 + 4 * (8 + 57) + 2);
+        assert!(trace.contains(
+            "Output:\n['0x115cc0f5e7d690413df64c6b9662e9cf2a3617f2743245519e19607a4417189a', "
+        ));
+    }
+
+    #[test]
+    fn test_constants() {
+        let c: serde_json::Value =
+            serde_json::from_str(&run_args(&["constants", "3"]).unwrap()).unwrap();
+        assert_eq!(c["C"].as_array().unwrap().len(), (8 + 57) * 3);
+        assert_eq!(c["M"].as_array().unwrap().len(), 3);
+        assert!(run_args(&["constants", "18"]).is_err());
+    }
+
+    #[test]
+    fn test_vectors() {
+        let v: serde_json::Value = serde_json::from_str(&run_args(&["vectors"]).unwrap()).unwrap();
+        let v = v.as_array().unwrap();
+        assert_eq!(v.len(), 16 * 3);
+        assert_eq!(v[3]["inputs"], serde_json::json!(["1", "2"]));
+        assert_eq!(v[3]["output"], H_1_2.trim());
+    }
+
+    #[test]
+    fn test_r1cs() {
+        let r1cs = TempFile::new("test_r1cs.r1cs");
+        assert_eq!(run_args(&["r1cs", "2", r1cs.path()]).unwrap(), "");
+        assert_eq!(
+            fs::read(r1cs.path()).unwrap(),
+            Poseidon::new().r1cs(2).unwrap().to_bytes()
+        );
+        assert!(run_args(&["r1cs", "17", r1cs.path()]).is_err());
+        assert!(run_args(&["r1cs", "2"]).is_err());
+    }
+
+    #[test]
+    fn test_wtns() {
+        let wtns = TempFile::new("test_wtns.wtns");
+        assert_eq!(run_args(&["wtns", wtns.path(), "1", "2"]).unwrap(), "");
+        let witness = Poseidon::new()
+            .witness(&[Fr::from_u64(1), Fr::from_u64(2)])
+            .unwrap();
+        assert_eq!(fs::read(wtns.path()).unwrap(), wtns_bytes(&witness));
+        assert!(run_args(&["wtns"]).is_err());
+    }
+
+    #[cfg(feature = "codegen")]
+    #[test]
+    fn test_circom() {
+        let template = run_args(&["circom", "2"]).unwrap();
+        assert!(template.starts_with("pragma circom 2.0.0;"));
+        assert!(template.contains("assert(nInputs == 2);"));
+        assert!(run_args(&["circom", "17"]).is_err());
+        assert!(run_args(&["circom"]).is_err());
+    }
+
+    #[test]
+    fn test_help() {
+        assert_eq!(run_args(&["help"]).unwrap(), format!("{}\n", USAGE));
+        assert!(run_args(&["foo"]).is_err());
+        assert!(run_args(&[]).is_err());
+    }
+}
@@ -0,0 +1,522 @@
+// Generators of the circom template, the EVM contract and its Solidity
+// interface of Poseidon::hash for a number of inputs, from Constants, as
+// circomlib's poseidon.circom and poseidon_gencontract.js do.
+//
+// The contract follows poseidon_gencontract.js: it keeps the state and the
+// modulus on the stack, M in memory and the round constants in the code, and
+// jumps to a single mix subroutine after the sbox of each round. It answers
+// poseidon(uint256[n]) and poseidon(bytes32[n]). The subroutine needs DUP16
+// to reach the last element of the state, so it supports up to 6 inputs.
+use alloc::{format, string::String, vec, vec::Vec};
+use core::fmt::Write;
+
+use crate::fr::*;
+use sha3::{Digest, Keccak256};
+
+use crate::convert::Hex;
+use crate::permutation;
+use crate::{Constants, Fr, PoseidonError};
+
+// the max number of inputs of evm_bytecode
+pub const EVM_MAX_INPUTS: usize = 6;
+
+fn check_n_inputs(constants: &Constants, n_inputs: usize) -> Result<(), PoseidonError> {
+    if n_inputs == 0 {
+        return Err(PoseidonError::EmptyInput);
+    }
+    let max = constants.n_rounds_p.len();
+    if n_inputs > max {
+        return Err(PoseidonError::InputTooLong { max });
+    }
+    Ok(())
+}
+
+fn hex_list(v: &[Fr]) -> String {
+    let mut s = String::new();
+    for (i, e) in v.iter().enumerate() {
+        if i > 0 {
+            s.push_str(",\n        ");
+        }
+        write!(s, "{}", Hex(*e)).unwrap();
+    }
+    s
+}
+
+// the circom template Poseidon(n_inputs), with the constants of its width
+pub fn circom_template(constants: &Constants, n_inputs: usize) -> Result<String, PoseidonError> {
+    check_n_inputs(constants, n_inputs)?;
+    let t = n_inputs + 1;
+    let c = &constants.c[t - 2];
+    let m: Vec<String> = constants.m[t - 2]
+        .iter()
+        .map(|row| format!("[\n        {}\n    ]", hex_list(row)))
+        .collect();
+    Ok(format!(
+        r#"pragma circom 2.0.0;
+
+function POSEIDON_C() {{
+    return [
+        {c}
+    ];
+}}
+
+function POSEIDON_M() {{
+    return [
+    {m}
+    ];
+}}
+
+template Sigma() {{
+    signal input in;
+    signal output out;
+
+    signal in2;
+    signal in4;
+
+    in2 <== in * in;
+    in4 <== in2 * in2;
+    out <== in4 * in;
+}}
+
+template Ark(t, C, r) {{
+    signal input in[t];
+    signal output out[t];
+
+    for (var i = 0; i < t; i++) {{
+        out[i] <== in[i] + C[i + r];
+    }}
+}}
+
+template Mix(t, M) {{
+    signal input in[t];
+    signal output out[t];
+
+    var lc;
+    for (var i = 0; i < t; i++) {{
+        lc = 0;
+        for (var j = 0; j < t; j++) {{
+            lc += M[i][j] * in[j];
+        }}
+        out[i] <== lc;
+    }}
+}}
+
+template Poseidon(nInputs) {{
+    assert(nInputs == {n_inputs});
+
+    signal input inputs[nInputs];
+    signal output out;
+
+    var t = nInputs + 1;
+    var nRoundsF = {n_rounds_f};
+    var nRoundsP = {n_rounds_p};
+    var C[{n_c}] = POSEIDON_C();
+    var M[{t}][{t}] = POSEIDON_M();
+
+    component ark[nRoundsF + nRoundsP];
+    component sigmaF[nRoundsF][t];
+    component sigmaP[nRoundsP];
+    component mix[nRoundsF + nRoundsP];
+
+    var k;
+    for (var i = 0; i < nRoundsF + nRoundsP; i++) {{
+        ark[i] = Ark(t, C, t * i);
+        for (var j = 0; j < t; j++) {{
+            if (i == 0) {{
+                if (j == 0) {{
+                    ark[i].in[j] <== 0;
+                }} else {{
+                    ark[i].in[j] <== inputs[j - 1];
+                }}
+            }} else {{
+                ark[i].in[j] <== mix[i - 1].out[j];
+            }}
+        }}
+
+        mix[i] = Mix(t, M);
+        if (i < nRoundsF / 2 || i >= nRoundsP + nRoundsF / 2) {{
+            k = i < nRoundsF / 2 ? i : i - nRoundsP;
+            for (var j = 0; j < t; j++) {{
+                sigmaF[k][j] = Sigma();
+                sigmaF[k][j].in <== ark[i].out[j];
+                mix[i].in[j] <== sigmaF[k][j].out;
+            }}
+        }} else {{
+            k = i - nRoundsF / 2;
+            sigmaP[k] = Sigma();
+            sigmaP[k].in <== ark[i].out[0];
+            mix[i].in[0] <== sigmaP[k].out;
+            for (var j = 1; j < t; j++) {{
+                mix[i].in[j] <== ark[i].out[j];
+            }}
+        }}
+    }}
+
+    out <== mix[nRoundsF + nRoundsP - 1].out[0];
+}}
+"#,
+        c = hex_list(c),
+        m = m.join(",\n    "),
+        n_inputs = n_inputs,
+        n_rounds_f = constants.n_rounds_f,
+        n_rounds_p = constants.n_rounds_p[t - 2],
+        n_c = c.len(),
+        t = t,
+    ))
+}
+
+// the Solidity interface of the contract of evm_bytecode
+pub fn solidity_interface(n_inputs: usize) -> String {
+    format!(
+        "// SPDX-License-Identifier: Apache-2.0
+pragma solidity >=0.7.0;
+
+interface PoseidonT{t} {{
+    function poseidon(uint256[{n}] calldata inputs) external pure returns (uint256);
+
+    function poseidon(bytes32[{n}] calldata inputs) external pure returns (bytes32);
+}}
+",
+        t = n_inputs + 1,
+        n = n_inputs
+    )
+}
+
+fn selector(signature: &str) -> [u8; 4] {
+    let mut s = [0u8; 4];
+    s.copy_from_slice(&Keccak256::digest(signature.as_bytes())[..4]);
+    s
+}
+
+const ADDMOD: u8 = 0x08;
+const MULMOD: u8 = 0x09;
+const DIV: u8 = 0x04;
+const EQ: u8 = 0x14;
+const OR: u8 = 0x17;
+const CALLDATALOAD: u8 = 0x35;
+const POP: u8 = 0x50;
+const MLOAD: u8 = 0x51;
+const MSTORE: u8 = 0x52;
+const JUMP: u8 = 0x56;
+const JUMPI: u8 = 0x57;
+const JUMPDEST: u8 = 0x5b;
+const RETURN: u8 = 0xf3;
+const INVALID: u8 = 0xfe;
+
+// assembler with labels, which are pushed as 2 byte offsets
+struct Asm {
+    code: Vec<u8>,
+    labels: Vec<Option<usize>>,
+    refs: Vec<(usize, usize)>,
+}
+
+impl Asm {
+    fn op(&mut self, op: u8) {
+        self.code.push(op);
+    }
+
+    // PUSHn of the n bytes of v
+    fn push(&mut self, v: &[u8]) {
+        self.code.push(0x5f + v.len() as u8);
+        self.code.extend_from_slice(v);
+    }
+
+    fn push_u16(&mut self, n: u16) {
+        if n < 0x100 {
+            self.push(&[n as u8]);
+        } else {
+            self.push(&n.to_be_bytes());
+        }
+    }
+
+    // DUPn and SWAPn, with n from 1 to 16
+    fn dup(&mut self, n: usize) {
+        self.op(0x7f + n as u8);
+    }
+
+    fn swap(&mut self, n: usize) {
+        self.op(0x8f + n as u8);
+    }
+
+    fn label(&mut self) -> usize {
+        self.labels.push(None);
+        self.labels.len() - 1
+    }
+
+    fn mark(&mut self, label: usize) {
+        self.labels[label] = Some(self.code.len());
+        self.op(JUMPDEST);
+    }
+
+    fn push_label(&mut self, label: usize) {
+        self.refs.push((self.code.len() + 1, label));
+        self.push(&[0, 0]);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        for (pos, label) in &self.refs {
+            let offset = self.labels[*label].expect("Unmarked label") as u16;
+            self.code[*pos..*pos + 2].copy_from_slice(&offset.to_be_bytes());
+        }
+        self.code
+    }
+}
+
+// the creation code of the contract for n_inputs inputs
+pub fn evm_bytecode(constants: &Constants, n_inputs: usize) -> Result<Vec<u8>, PoseidonError> {
+    check_n_inputs(constants, n_inputs)?;
+    if n_inputs > EVM_MAX_INPUTS {
+        return Err(PoseidonError::InputTooLong {
+            max: EVM_MAX_INPUTS,
+        });
+    }
+    let t = n_inputs + 1;
+    let n_rounds_f = constants.n_rounds_f;
+    let n_rounds_p = constants.n_rounds_p[t - 2];
+    let c = &constants.c[t - 2];
+    let m = &constants.m[t - 2];
+    // memory address of M[i][j], 0 holds the return address of mix
+    let m_addr = |i: usize, j: usize| ((1 + i * t + j) * 32) as u16;
+
+    let mut a = Asm {
+        code: Vec::new(),
+        labels: Vec::new(),
+        refs: Vec::new(),
+    };
+    let start = a.label();
+    let mix = a.label();
+
+    // dispatch on the selector
+    a.push(&[0]);
+    a.op(CALLDATALOAD);
+    let mut shift = [0u8; 29];
+    shift[0] = 1;
+    a.push(&shift);
+    a.swap(1);
+    a.op(DIV);
+    a.dup(1);
+    a.push(&selector(&format!("poseidon(uint256[{}])", n_inputs)));
+    a.op(EQ);
+    a.swap(1);
+    a.push(&selector(&format!("poseidon(bytes32[{}])", n_inputs)));
+    a.op(EQ);
+    a.op(OR);
+    a.push_label(start);
+    a.op(JUMPI);
+    a.op(INVALID);
+    a.mark(start);
+
+    for (i, row) in m.iter().enumerate() {
+        for (j, mij) in row.iter().enumerate() {
+            a.push(&mij.to_bytes_be());
+            a.push_u16(m_addr(i, j));
+            a.op(MSTORE);
+        }
+    }
+
+    // stack: state[0], ..., state[t - 1], q
+    let mut p_minus_1 = Fr::one();
+    p_minus_1.negate();
+    let mut q = p_minus_1.to_bytes_be();
+    q[31] += 1;
+    a.push(&q);
+    for i in (0..n_inputs).rev() {
+        a.push_u16((4 + 32 * i) as u16);
+        a.op(CALLDATALOAD);
+    }
+    a.push(&[0]);
+
+    for r in 0..(n_rounds_f + n_rounds_p) {
+        // ark
+        for i in 0..t {
+            a.dup(t + 1);
+            a.push(&c[r * t + i].to_bytes_be());
+            a.dup(3 + i);
+            a.op(ADDMOD);
+            a.swap(1 + i);
+            a.op(POP);
+        }
+        // sbox
+        let full = permutation::is_full_round(r, n_rounds_f, n_rounds_p);
+        for p in 0..(if full { t } else { 1 }) {
+            a.dup(t + 1);
+            a.dup(2 + p);
+            a.dup(2);
+            a.dup(1);
+            a.dup(3);
+            a.dup(1);
+            a.op(MULMOD);
+            a.dup(1);
+            a.op(MULMOD);
+            a.op(MULMOD);
+            a.swap(1 + p);
+            a.op(POP);
+        }
+        // mix
+        let ret = a.label();
+        a.push_label(ret);
+        a.push(&[0]);
+        a.op(MSTORE);
+        a.push_label(mix);
+        a.op(JUMP);
+        a.mark(ret);
+    }
+
+    a.push(&[0]);
+    a.op(MSTORE);
+    a.push(&[0x20]);
+    a.push(&[0]);
+    a.op(RETURN);
+
+    // mix subroutine, pushes the new state over the old one and then drops
+    // the old one
+    a.mark(mix);
+    for i in 0..t {
+        for j in 0..t {
+            if j == 0 {
+                a.dup(1 + i + t);
+                a.push_u16(m_addr(i, j));
+                a.op(MLOAD);
+                a.dup(3 + i + j);
+                a.op(MULMOD);
+            } else {
+                a.dup(2 + i + t);
+                a.push_u16(m_addr(i, j));
+                a.op(MLOAD);
+                a.dup(4 + i + j);
+                a.op(MULMOD);
+                a.dup(3 + i + t);
+                a.swap(2);
+                a.op(ADDMOD);
+            }
+        }
+    }
+    for i in 0..t {
+        a.swap((t - i) + (t - i - 1));
+        a.op(POP);
+    }
+    a.push(&[0]);
+    a.op(MLOAD);
+    a.op(JUMP);
+
+    // creation code, which returns the runtime code after it
+    let runtime = a.finish();
+    let len = (runtime.len() as u16).to_be_bytes();
+    let mut code = vec![
+        0x61, len[0], len[1], // PUSH2 len
+        0x80,   // DUP1
+        0x61, 0x00, 0x0d, // PUSH2 13
+        0x60, 0x00, // PUSH1 0
+        0x39, // CODECOPY
+        0x60, 0x00, // PUSH1 0
+        RETURN,
+    ];
+    code.extend_from_slice(&runtime);
+    Ok(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{load_constants, Poseidon};
+    use revm::primitives::{ExecutionResult, Output, TxKind};
+    use revm::{Evm, InMemoryDB};
+
+    #[test]
+    fn test_circom_template() {
+        let constants = load_constants().unwrap();
+        let s = circom_template(&constants, 2).unwrap();
+        assert!(s.starts_with("pragma circom 2.0.0;\n"));
+        assert!(s.contains("template Poseidon(nInputs) {\n    assert(nInputs == 2);"));
+        assert!(s.contains("var nRoundsP = 57;"));
+        assert!(s.contains("var C[195] = POSEIDON_C();"));
+        assert!(s.contains(&Hex(constants.c[1][194]).to_string()));
+        assert!(s.contains(&Hex(constants.m[1][2][2]).to_string()));
+        assert_eq!(s.matches("0x").count(), 195 + 9);
+        assert_eq!(s.matches('{').count(), s.matches('}').count());
+        assert_eq!(s.matches('[').count(), s.matches(']').count());
+
+        assert_eq!(
+            circom_template(&constants, 0).err(),
+            Some(PoseidonError::EmptyInput)
+        );
+        assert_eq!(
+            circom_template(&constants, 17).err(),
+            Some(PoseidonError::InputTooLong { max: 16 })
+        );
+    }
+
+    fn call(evm: &mut Evm<'_, (), InMemoryDB>, data: Vec<u8>) -> Option<Vec<u8>> {
+        evm.tx_mut().data = data.into();
+        match evm.transact_commit().unwrap() {
+            ExecutionResult::Success {
+                output: Output::Call(out),
+                ..
+            } => Some(out.to_vec()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_evm_bytecode() {
+        let constants = load_constants().unwrap();
+        let poseidon = Poseidon::new();
+        let mut p_minus_1 = Fr::one();
+        p_minus_1.negate();
+
+        for n in 1..=EVM_MAX_INPUTS {
+            let code = evm_bytecode(&constants, n).unwrap();
+            let mut evm = Evm::builder()
+                .with_db(InMemoryDB::default())
+                .modify_tx_env(|tx| {
+                    tx.transact_to = TxKind::Create;
+                    tx.data = code.into();
+                    tx.gas_limit = 10_000_000;
+                })
+                .build();
+            let address = match evm.transact_commit().unwrap() {
+                ExecutionResult::Success {
+                    output: Output::Create(_, Some(address)),
+                    ..
+                } => address,
+                r => panic!("Deployment failed: {:?}", r),
+            };
+            evm.tx_mut().transact_to = TxKind::Call(address);
+
+            let cases = vec![
+                (1..=n as u64).map(Fr::from_u64).collect(),
+                vec![p_minus_1; n],
+            ];
+            for inp in cases {
+                let expected = poseidon.hash(inp.clone()).unwrap().to_bytes_be().to_vec();
+                for signature in ["uint256", "bytes32"].iter() {
+                    let mut data = selector(&format!("poseidon({}[{}])", signature, n)).to_vec();
+                    for e in &inp {
+                        data.extend_from_slice(&e.to_bytes_be());
+                    }
+                    assert_eq!(call(&mut evm, data), Some(expected.clone()));
+                }
+            }
+
+            let data = selector(&format!("poseidon(uint256[{}])", n + 1)).to_vec();
+            assert_eq!(call(&mut evm, data), None);
+        }
+
+        assert_eq!(
+            evm_bytecode(&constants, 7).err(),
+            Some(PoseidonError::InputTooLong { max: 6 })
+        );
+        assert_eq!(
+            solidity_interface(2),
+            "// SPDX-License-Identifier: Apache-2.0
+pragma solidity >=0.7.0;
+
+interface PoseidonT3 {
+    function poseidon(uint256[2] calldata inputs) external pure returns (uint256);
+
+    function poseidon(bytes32[2] calldata inputs) external pure returns (bytes32);
+}
+"
+        );
+    }
+}
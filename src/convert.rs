@@ -0,0 +1,490 @@
+// Conversions between Fr and bytes, integers and strings.
+//
+// Byte encodings are 32 bytes long and must be canonical, that is, smaller
+// than the modulus. The little-endian encoding is the one used by
+// go-iden3-crypto and circomlibjs.
+//
+// Parsing is strict about the value: unlike PrimeField::from_str, which
+// reduces its input, a value that is not smaller than the modulus is an
+// error, so that two different numbers never parse to the same element. The
+// textual forms are not unique though: decimal rejects signs and leading
+// zeros, but hex takes the 0x prefix or not, upper or lower case digits and
+// leading zeros up to 64 digits, and JSON takes decimal or hex strings and
+// unsigned integers. Compare the parsed elements, not the strings.
+use core::convert::TryFrom;
+use core::fmt;
+use core::str::FromStr;
+
+use crate::fr::*;
+#[cfg(feature = "num-bigint")]
+use num_bigint::BigUint;
+#[cfg(feature = "json")]
+use serde_json::Value;
+
+use crate::{Fr, FrRepr};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    Empty,
+    InvalidCharacter(char),
+    LeadingZeros,
+    TooLong,
+    WrongLength(usize),
+    NonCanonical,
+    // the JSON value is not a string nor an unsigned integer
+    #[cfg(feature = "json")]
+    Json(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "Empty input"),
+            ParseError::InvalidCharacter(c) => write!(f, "Invalid character {:?}", c),
+            ParseError::LeadingZeros => write!(f, "Leading zeros"),
+            ParseError::TooLong => write!(f, "Input too long"),
+            ParseError::WrongLength(n) => write!(f, "Wrong length {}, expected 32 bytes", n),
+            ParseError::NonCanonical => write!(f, "Non canonical field element"),
+            #[cfg(feature = "json")]
+            ParseError::Json(v) => write!(f, "Invalid JSON field element {}", v),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseError {}
+
+fn check_digits(digits: &str, radix: u32) -> Result<(), ParseError> {
+    if digits.is_empty() {
+        return Err(ParseError::Empty);
+    }
+    match digits.chars().find(|c| !c.is_digit(radix)) {
+        Some(c) => Err(ParseError::InvalidCharacter(c)),
+        None => Ok(()),
+    }
+}
+
+// value of the (already checked) digits, None if it needs more than 256 bits
+fn digits_to_repr(digits: &str, radix: u32) -> Option<FrRepr> {
+    let mut r = [0u64; 4];
+    for c in digits.chars() {
+        let mut carry = c.to_digit(radix).unwrap() as u128;
+        for limb in r.iter_mut() {
+            let v = *limb as u128 * radix as u128 + carry;
+            *limb = v as u64;
+            carry = v >> 64;
+        }
+        if carry != 0 {
+            return None;
+        }
+    }
+    Some(FrRepr(r))
+}
+
+impl Fr {
+    pub fn to_bytes_le(&self) -> [u8; 32] {
+        let mut b = [0u8; 32];
+        for (chunk, limb) in b.chunks_exact_mut(8).zip(self.into_repr().0.iter()) {
+            chunk.copy_from_slice(&limb.to_le_bytes());
+        }
+        b
+    }
+
+    pub fn to_bytes_be(&self) -> [u8; 32] {
+        let mut b = self.to_bytes_le();
+        b.reverse();
+        b
+    }
+
+    pub fn from_bytes_le(b: &[u8; 32]) -> Result<Fr, ParseError> {
+        let mut repr = FrRepr::from(0);
+        for (limb, chunk) in repr.0.iter_mut().zip(b.chunks_exact(8)) {
+            *limb = u64::from_le_bytes(<[u8; 8]>::try_from(chunk).unwrap());
+        }
+        Fr::from_repr(repr).map_err(|_| ParseError::NonCanonical)
+    }
+
+    pub fn from_bytes_be(b: &[u8; 32]) -> Result<Fr, ParseError> {
+        let mut le = *b;
+        le.reverse();
+        Fr::from_bytes_le(&le)
+    }
+
+    // same as from_bytes_le, for slices that must be 32 bytes long
+    pub fn from_slice_le(b: &[u8]) -> Result<Fr, ParseError> {
+        let b = <&[u8; 32]>::try_from(b).map_err(|_| ParseError::WrongLength(b.len()))?;
+        Fr::from_bytes_le(b)
+    }
+
+    pub fn from_slice_be(b: &[u8]) -> Result<Fr, ParseError> {
+        let b = <&[u8; 32]>::try_from(b).map_err(|_| ParseError::WrongLength(b.len()))?;
+        Fr::from_bytes_be(b)
+    }
+
+    // Decimal digits only, without sign nor leading zeros.
+    pub fn parse_dec(s: &str) -> Result<Fr, ParseError> {
+        check_digits(s, 10)?;
+        if s.len() > 1 && s.starts_with('0') {
+            return Err(ParseError::LeadingZeros);
+        }
+        let repr = digits_to_repr(s, 10).ok_or(ParseError::NonCanonical)?;
+        Fr::from_repr(repr).map_err(|_| ParseError::NonCanonical)
+    }
+
+    // Hex digits in any case with an optional 0x prefix, up to 64 digits.
+    pub fn parse_hex(s: &str) -> Result<Fr, ParseError> {
+        let digits = s.strip_prefix("0x").unwrap_or(s);
+        check_digits(digits, 16)?;
+        if digits.len() > 64 {
+            return Err(ParseError::TooLong);
+        }
+        let repr = digits_to_repr(digits, 16).unwrap();
+        Fr::from_repr(repr).map_err(|_| ParseError::NonCanonical)
+    }
+
+    // A JSON string, parsed as hex if it has the 0x prefix and as decimal
+    // otherwise, or an unsigned integer that fits in a u64.
+    #[cfg(feature = "json")]
+    pub fn from_json(v: &Value) -> Result<Fr, ParseError> {
+        match v {
+            Value::String(s) if s.starts_with("0x") => Fr::parse_hex(s),
+            Value::String(s) => Fr::parse_dec(s),
+            Value::Number(n) => match n.as_u64() {
+                Some(n) => Ok(Fr::from_u64(n)),
+                None => Err(ParseError::Json(v.to_string())),
+            },
+            _ => Err(ParseError::Json(v.to_string())),
+        }
+    }
+
+    // parses a JSON array of elements, as accepted by from_json
+    #[cfg(feature = "json")]
+    pub fn vec_from_json(s: &str) -> Result<Vec<Fr>, ParseError> {
+        let v: Value = serde_json::from_str(s).map_err(|e| ParseError::Json(e.to_string()))?;
+        match v {
+            Value::Array(a) => a.iter().map(Fr::from_json).collect(),
+            _ => Err(ParseError::Json(v.to_string())),
+        }
+    }
+
+    pub fn from_u64(n: u64) -> Fr {
+        Fr::from_repr(FrRepr::from(n)).unwrap()
+    }
+
+    pub fn from_u128(n: u128) -> Fr {
+        Fr::from_repr(FrRepr([n as u64, (n >> 64) as u64, 0, 0])).unwrap()
+    }
+
+    // None if the element does not fit in a u64
+    pub fn to_u64(&self) -> Option<u64> {
+        let repr = self.into_repr();
+        if repr.0[1..].iter().any(|l| *l != 0) {
+            return None;
+        }
+        Some(repr.0[0])
+    }
+
+    // None if the element does not fit in a u128
+    pub fn to_u128(&self) -> Option<u128> {
+        let repr = self.into_repr();
+        if repr.0[2..].iter().any(|l| *l != 0) {
+            return None;
+        }
+        Some(repr.0[0] as u128 | (repr.0[1] as u128) << 64)
+    }
+
+    #[cfg(feature = "num-bigint")]
+    pub fn to_biguint(&self) -> BigUint {
+        BigUint::from_bytes_le(&self.to_bytes_le())
+    }
+
+    #[cfg(feature = "num-bigint")]
+    pub fn from_biguint(n: &BigUint) -> Result<Fr, ParseError> {
+        let le = n.to_bytes_le();
+        if le.len() > 32 {
+            return Err(ParseError::NonCanonical);
+        }
+        let mut b = [0u8; 32];
+        b[..le.len()].copy_from_slice(&le);
+        Fr::from_bytes_le(&b)
+    }
+}
+
+impl From<u64> for Fr {
+    fn from(n: u64) -> Fr {
+        Fr::from_u64(n)
+    }
+}
+
+impl From<u128> for Fr {
+    fn from(n: u128) -> Fr {
+        Fr::from_u128(n)
+    }
+}
+
+#[cfg(feature = "num-bigint")]
+impl From<&Fr> for BigUint {
+    fn from(e: &Fr) -> BigUint {
+        e.to_biguint()
+    }
+}
+
+#[cfg(feature = "num-bigint")]
+impl From<Fr> for BigUint {
+    fn from(e: Fr) -> BigUint {
+        e.to_biguint()
+    }
+}
+
+#[cfg(feature = "num-bigint")]
+impl TryFrom<&BigUint> for Fr {
+    type Error = ParseError;
+
+    fn try_from(n: &BigUint) -> Result<Fr, ParseError> {
+        Fr::from_biguint(n)
+    }
+}
+
+// Decimal representation of an element, as used by circom and snarkjs,
+// instead of the Fr(0x..) of Display.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Dec(pub Fr);
+
+impl fmt::Display for Dec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // base 10^19 digits, from the least significant one
+        const BASE: u128 = 10_000_000_000_000_000_000;
+        let mut n = self.0.into_repr().0;
+        let mut digits = [0u64; 5];
+        let mut len = 0;
+        loop {
+            let mut rem = 0u128;
+            for limb in n.iter_mut().rev() {
+                let v = rem << 64 | *limb as u128;
+                *limb = (v / BASE) as u64;
+                rem = v % BASE;
+            }
+            digits[len] = rem as u64;
+            len += 1;
+            if n.iter().all(|l| *l == 0) {
+                break;
+            }
+        }
+        write!(f, "{}", digits[len - 1])?;
+        for d in digits[..len - 1].iter().rev() {
+            write!(f, "{:019}", d)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Dec {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Dec, ParseError> {
+        Fr::parse_dec(s).map(Dec)
+    }
+}
+
+// 0x prefixed hexadecimal representation of an element, always with 64
+// digits. Parsing accepts up to 64 digits, with or without the prefix.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Hex(pub Fr);
+
+impl fmt::Display for Hex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "0x")?;
+        for limb in self.0.into_repr().0.iter().rev() {
+            write!(f, "{:016x}", limb)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Hex {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Hex, ParseError> {
+        Fr::parse_hex(s).map(Hex)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODULUS: &str =
+        "21888242871839275222246405745257275088548364400416034343698204186575808495617";
+
+    #[test]
+    fn test_bytes() {
+        let a = Fr::from_u64(0x0102);
+        let mut le = [0u8; 32];
+        le[0] = 2;
+        le[1] = 1;
+        assert_eq!(a.to_bytes_le(), le);
+        let mut be = le;
+        be.reverse();
+        assert_eq!(a.to_bytes_be(), be);
+        assert_eq!(Fr::from_bytes_le(&le).unwrap(), a);
+        assert_eq!(Fr::from_bytes_be(&be).unwrap(), a);
+
+        let mut p_minus_1 = Fr::one();
+        p_minus_1.negate();
+        let b = p_minus_1.to_bytes_le();
+        assert_eq!(Fr::from_bytes_le(&b).unwrap(), p_minus_1);
+
+        // the modulus itself is not canonical
+        let mut b = p_minus_1.to_bytes_be();
+        b[31] += 1;
+        assert_eq!(Fr::from_bytes_be(&b), Err(ParseError::NonCanonical));
+        assert_eq!(
+            Fr::from_bytes_le(&[0xff; 32]),
+            Err(ParseError::NonCanonical)
+        );
+
+        assert_eq!(Fr::from_slice_le(&le[..]).unwrap(), a);
+        assert_eq!(
+            Fr::from_slice_be(&be[1..]),
+            Err(ParseError::WrongLength(31))
+        );
+    }
+
+    #[test]
+    fn test_integers() {
+        assert_eq!(Fr::from(7u64), Fr::from_str("7").unwrap());
+        assert_eq!(Fr::from_u64(u64::MAX).to_u64(), Some(u64::MAX));
+        let n = u128::MAX - 5;
+        assert_eq!(Fr::from(n).to_u128(), Some(n));
+        assert_eq!(Fr::from(n).to_u64(), None);
+
+        let mut p_minus_1 = Fr::one();
+        p_minus_1.negate();
+        assert_eq!(p_minus_1.to_u128(), None);
+    }
+
+    #[cfg(feature = "num-bigint")]
+    #[test]
+    fn test_biguint() {
+        let mut p_minus_1 = Fr::one();
+        p_minus_1.negate();
+        let big = p_minus_1.to_biguint();
+        assert_eq!(
+            big + 1u32,
+            BigUint::parse_bytes(MODULUS.as_bytes(), 10).unwrap()
+        );
+        assert_eq!(Fr::try_from(&p_minus_1.to_biguint()).unwrap(), p_minus_1);
+        let p = BigUint::parse_bytes(MODULUS.as_bytes(), 10).unwrap();
+        assert_eq!(Fr::from_biguint(&p), Err(ParseError::NonCanonical));
+    }
+
+    #[test]
+    fn test_strings() {
+        let a = Fr::from_u64(255);
+        assert_eq!(Dec(a).to_string(), "255");
+        assert_eq!(
+            Hex(a).to_string(),
+            "0x00000000000000000000000000000000000000000000000000000000000000ff"
+        );
+        assert_eq!("255".parse::<Dec>().unwrap(), Dec(a));
+        assert_eq!("0xff".parse::<Hex>().unwrap(), Hex(a));
+        assert_eq!("ff".parse::<Hex>().unwrap(), Hex(a));
+
+        let mut p_minus_1 = Fr::one();
+        p_minus_1.negate();
+        let s = Dec(p_minus_1).to_string();
+        assert_eq!(s.parse::<Dec>().unwrap(), Dec(p_minus_1));
+        let s = Hex(p_minus_1).to_string();
+        assert_eq!(s.parse::<Hex>().unwrap(), Hex(p_minus_1));
+
+        assert_eq!(MODULUS.parse::<Dec>(), Err(ParseError::NonCanonical));
+        assert_eq!("12a".parse::<Dec>(), Err(ParseError::InvalidCharacter('a')));
+        assert_eq!(
+            "0xfg".parse::<Hex>(),
+            Err(ParseError::InvalidCharacter('g'))
+        );
+    }
+
+    #[test]
+    fn test_strict_parsing() {
+        // PrimeField::from_str reduces modulus + 2 to 2, parse_dec rejects it
+        let p_plus_2 =
+            "21888242871839275222246405745257275088548364400416034343698204186575808495619";
+        assert_eq!(Fr::from_str(p_plus_2).unwrap(), Fr::from_u64(2));
+        assert_eq!(Fr::parse_dec(p_plus_2), Err(ParseError::NonCanonical));
+        let long = "1".repeat(100);
+        assert_eq!(Fr::parse_dec(&long), Err(ParseError::NonCanonical));
+
+        assert_eq!(Fr::parse_dec("0").unwrap(), Fr::zero());
+        assert_eq!(Fr::parse_dec(""), Err(ParseError::Empty));
+        assert_eq!(Fr::parse_dec("02"), Err(ParseError::LeadingZeros));
+        assert_eq!(Fr::parse_dec("+2"), Err(ParseError::InvalidCharacter('+')));
+        assert_eq!(Fr::parse_dec("-2"), Err(ParseError::InvalidCharacter('-')));
+        assert_eq!(Fr::parse_dec(" 2"), Err(ParseError::InvalidCharacter(' ')));
+        assert_eq!(Fr::parse_dec("1_0"), Err(ParseError::InvalidCharacter('_')));
+
+        assert_eq!(Fr::parse_hex("0x0a").unwrap(), Fr::from_u64(10));
+        assert_eq!(Fr::parse_hex("0x"), Err(ParseError::Empty));
+        let mut p_minus_1 = Fr::one();
+        p_minus_1.negate();
+        let mut b = p_minus_1.to_bytes_be();
+        b[31] += 1;
+        let p_hex: String = b.iter().map(|b| format!("{:02x}", b)).collect();
+        assert_eq!(Fr::parse_hex(&p_hex), Err(ParseError::NonCanonical));
+        let long = format!("0x{}", "0".repeat(65));
+        assert_eq!(Fr::parse_hex(&long), Err(ParseError::TooLong));
+
+        // the accepted hex forms of the same element
+        for s in [
+            "0xff",
+            "ff",
+            "0xFF",
+            "0x00ff",
+            &Hex(Fr::from_u64(255)).to_string(),
+        ] {
+            assert_eq!(Fr::parse_hex(s).unwrap(), Fr::from_u64(255));
+        }
+        assert_eq!(
+            Fr::parse_hex("0Xff"),
+            Err(ParseError::InvalidCharacter('X'))
+        );
+        // the reason is kept when converted to a PoseidonError
+        let e = crate::PoseidonError::from(ParseError::LeadingZeros);
+        assert_eq!(
+            e,
+            crate::PoseidonError::InvalidFieldElement(ParseError::LeadingZeros)
+        );
+        assert_eq!(e.to_string(), "Invalid field element: Leading zeros");
+        assert_eq!(
+            crate::PoseidonError::from(ParseError::NonCanonical),
+            crate::PoseidonError::NonCanonical
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json() {
+        let v = Fr::vec_from_json(r#"["1", "0x02", 3]"#).unwrap();
+        assert_eq!(v, vec![Fr::from_u64(1), Fr::from_u64(2), Fr::from_u64(3)]);
+        let v = Fr::vec_from_json(r#"["3", "0x3", "0x0003", 3]"#).unwrap();
+        assert!(v.iter().all(|e| *e == Fr::from_u64(3)));
+        assert_eq!(
+            Fr::vec_from_json(r#"["03"]"#),
+            Err(ParseError::LeadingZeros)
+        );
+        assert_eq!(
+            Fr::vec_from_json(&format!(r#"["{}"]"#, MODULUS)),
+            Err(ParseError::NonCanonical)
+        );
+        assert!(matches!(
+            Fr::vec_from_json("[-1]"),
+            Err(ParseError::Json(_))
+        ));
+        assert!(matches!(
+            Fr::vec_from_json("[1.5]"),
+            Err(ParseError::Json(_))
+        ));
+        assert!(matches!(Fr::vec_from_json("{}"), Err(ParseError::Json(_))));
+        assert!(matches!(Fr::vec_from_json("["), Err(ParseError::Json(_))));
+    }
+}
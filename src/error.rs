@@ -0,0 +1,65 @@
+use core::fmt;
+
+use crate::convert::ParseError;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PoseidonError {
+    EmptyInput,
+    InputTooLong { max: usize },
+    // for hashers bound to a number of inputs
+    WrongInputsLength { expected: usize },
+    UnsupportedWidth(usize),
+    UnsupportedFrameSize(usize),
+    MalformedConstants(&'static str),
+    NonCanonical,
+    UnsupportedBackend,
+    InvalidDepth(usize),
+    IndexOutOfRange(usize),
+    TreeFull,
+    DuplicateValue,
+    ValueNotFound,
+    InvalidSize(usize),
+    InvalidProof(&'static str),
+    InvalidEncoding(&'static str),
+    InvalidFieldElement(ParseError),
+}
+
+impl fmt::Display for PoseidonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PoseidonError::EmptyInput => write!(f, "Empty input"),
+            PoseidonError::InputTooLong { max } => {
+                write!(f, "Input too long, max {} elements", max)
+            }
+            PoseidonError::WrongInputsLength { expected } => {
+                write!(f, "Wrong inputs length, expected {}", expected)
+            }
+            PoseidonError::UnsupportedWidth(t) => write!(f, "Unsupported width {}", t),
+            PoseidonError::UnsupportedFrameSize(n) => write!(f, "Unsupported frame size {}", n),
+            PoseidonError::MalformedConstants(e) => write!(f, "Malformed constants: {}", e),
+            PoseidonError::NonCanonical => write!(f, "Non canonical field element"),
+            PoseidonError::UnsupportedBackend => write!(f, "Backend not supported by the CPU"),
+            PoseidonError::InvalidDepth(d) => write!(f, "Wrong tree depth {}", d),
+            PoseidonError::IndexOutOfRange(i) => write!(f, "Index {} out of range", i),
+            PoseidonError::TreeFull => write!(f, "Tree is full"),
+            PoseidonError::DuplicateValue => write!(f, "Duplicated value"),
+            PoseidonError::ValueNotFound => write!(f, "Value not found"),
+            PoseidonError::InvalidSize(n) => write!(f, "Wrong size {}", n),
+            PoseidonError::InvalidProof(e) => write!(f, "Invalid proof: {}", e),
+            PoseidonError::InvalidEncoding(e) => write!(f, "Invalid encoding: {}", e),
+            PoseidonError::InvalidFieldElement(e) => write!(f, "Invalid field element: {}", e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PoseidonError {}
+
+impl From<ParseError> for PoseidonError {
+    fn from(e: ParseError) -> PoseidonError {
+        match e {
+            ParseError::NonCanonical => PoseidonError::NonCanonical,
+            e => PoseidonError::InvalidFieldElement(e),
+        }
+    }
+}
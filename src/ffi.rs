@@ -0,0 +1,217 @@
+// C ABI. Field elements are passed as 32 byte little-endian buffers, and
+// arrays of them as contiguous buffers of 32 * n bytes. Every function
+// returns a PoseidonStatus and writes the 32 byte hash to out only on
+// success. The header is generated with `cbindgen -o ffi/poseidon.h`.
+use std::os::raw::c_char;
+use std::slice;
+use std::sync::OnceLock;
+
+use crate::{Fr, Poseidon, PoseidonError};
+
+static POSEIDON: OnceLock<Poseidon> = OnceLock::new();
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PoseidonStatus {
+    Ok = 0,
+    NullPointer = 1,
+    // the length of a field elements buffer is not a multiple of 32
+    InvalidLength = 2,
+    EmptyInput = 3,
+    InputTooLong = 4,
+    NonCanonical = 5,
+    UnsupportedFrameSize = 6,
+    Error = 255,
+}
+
+impl From<PoseidonError> for PoseidonStatus {
+    fn from(e: PoseidonError) -> PoseidonStatus {
+        match e {
+            PoseidonError::EmptyInput => PoseidonStatus::EmptyInput,
+            PoseidonError::InputTooLong { .. } => PoseidonStatus::InputTooLong,
+            PoseidonError::NonCanonical => PoseidonStatus::NonCanonical,
+            PoseidonError::UnsupportedFrameSize(_) => PoseidonStatus::UnsupportedFrameSize,
+            _ => PoseidonStatus::Error,
+        }
+    }
+}
+
+fn poseidon() -> &'static Poseidon {
+    POSEIDON.get_or_init(Poseidon::new)
+}
+
+// a null pointer is accepted for empty buffers
+unsafe fn bytes<'a>(ptr: *const u8, len: usize) -> Result<&'a [u8], PoseidonStatus> {
+    if len == 0 {
+        return Ok(&[]);
+    }
+    if ptr.is_null() {
+        return Err(PoseidonStatus::NullPointer);
+    }
+    Ok(slice::from_raw_parts(ptr, len))
+}
+
+unsafe fn elements(ptr: *const u8, len: usize) -> Result<Vec<Fr>, PoseidonStatus> {
+    if !len.is_multiple_of(32) {
+        return Err(PoseidonStatus::InvalidLength);
+    }
+    bytes(ptr, len)?
+        .chunks(32)
+        .map(|b| Fr::from_slice_le(b).map_err(|e| PoseidonError::from(e).into()))
+        .collect()
+}
+
+unsafe fn write_out(out: *mut u8, h: Result<Fr, PoseidonStatus>) -> PoseidonStatus {
+    if out.is_null() {
+        return PoseidonStatus::NullPointer;
+    }
+    match h {
+        Ok(h) => {
+            slice::from_raw_parts_mut(out, 32).copy_from_slice(&h.to_bytes_le());
+            PoseidonStatus::Ok
+        }
+        Err(e) => e,
+    }
+}
+
+/// Hashes `inputs_len / 32` field elements.
+///
+/// # Safety
+/// `inputs` must point to `inputs_len` readable bytes and `out` to 32
+/// writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn poseidon_hash(
+    inputs: *const u8,
+    inputs_len: usize,
+    out: *mut u8,
+) -> PoseidonStatus {
+    let h = elements(inputs, inputs_len)
+        .and_then(|inp| poseidon().hash(inp).map_err(PoseidonStatus::from));
+    write_out(out, h)
+}
+
+/// Hashes a message of any length, as `HashBytesX` from go-iden3-crypto.
+///
+/// # Safety
+/// `msg` must point to `msg_len` readable bytes and `out` to 32 writable
+/// bytes.
+#[no_mangle]
+pub unsafe extern "C" fn poseidon_hash_bytes(
+    msg: *const u8,
+    msg_len: usize,
+    frame_size: usize,
+    out: *mut u8,
+) -> PoseidonStatus {
+    let h = bytes(msg, msg_len).and_then(|msg| {
+        poseidon()
+            .hash_bytes_x(msg, frame_size)
+            .map_err(PoseidonStatus::from)
+    });
+    write_out(out, h)
+}
+
+/// Hashes any number of field elements, as `SpongeHashX` from
+/// go-iden3-crypto.
+///
+/// # Safety
+/// `inputs` must point to `inputs_len` readable bytes and `out` to 32
+/// writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn poseidon_sponge_hash(
+    inputs: *const u8,
+    inputs_len: usize,
+    frame_size: usize,
+    out: *mut u8,
+) -> PoseidonStatus {
+    let h = elements(inputs, inputs_len).and_then(|inp| {
+        poseidon()
+            .sponge_hash(&inp, frame_size)
+            .map_err(PoseidonStatus::from)
+    });
+    write_out(out, h)
+}
+
+/// Static, nul terminated description of a status. Takes the status as an
+/// integer, so that any value is accepted, and describes the ones out of
+/// range as an unknown status.
+#[no_mangle]
+pub extern "C" fn poseidon_status_message(status: u32) -> *const c_char {
+    const MESSAGES: [(PoseidonStatus, &[u8]); 8] = [
+        (PoseidonStatus::Ok, b"Ok\0"),
+        (PoseidonStatus::NullPointer, b"Null pointer\0"),
+        (
+            PoseidonStatus::InvalidLength,
+            b"Length not multiple of 32\0",
+        ),
+        (PoseidonStatus::EmptyInput, b"Empty input\0"),
+        (PoseidonStatus::InputTooLong, b"Input too long\0"),
+        (
+            PoseidonStatus::NonCanonical,
+            b"Non canonical field element\0",
+        ),
+        (
+            PoseidonStatus::UnsupportedFrameSize,
+            b"Unsupported frame size\0",
+        ),
+        (PoseidonStatus::Error, b"Error\0"),
+    ];
+    let msg: &'static [u8] = MESSAGES
+        .iter()
+        .find(|(s, _)| *s as u32 == status)
+        .map_or(b"Unknown status\0", |(_, msg)| msg);
+    msg.as_ptr() as *const c_char
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+    use std::ptr;
+
+    #[test]
+    fn test_ffi() {
+        let mut inp = [0u8; 64];
+        inp[0] = 1;
+        inp[32] = 2;
+        let mut out = [0u8; 32];
+        let status = unsafe { poseidon_hash(inp.as_ptr(), inp.len(), out.as_mut_ptr()) };
+        assert_eq!(status, PoseidonStatus::Ok);
+        let expected = Poseidon::new()
+            .hash(vec![Fr::from_u64(1), Fr::from_u64(2)])
+            .unwrap();
+        assert_eq!(out, expected.to_bytes_le());
+
+        let status = unsafe { poseidon_hash(inp.as_ptr(), 63, out.as_mut_ptr()) };
+        assert_eq!(status, PoseidonStatus::InvalidLength);
+        let status = unsafe { poseidon_hash(ptr::null(), 0, out.as_mut_ptr()) };
+        assert_eq!(status, PoseidonStatus::EmptyInput);
+        let status = unsafe { poseidon_hash(inp.as_ptr(), inp.len(), ptr::null_mut()) };
+        assert_eq!(status, PoseidonStatus::NullPointer);
+        let status = unsafe { poseidon_hash(ptr::null(), 32, out.as_mut_ptr()) };
+        assert_eq!(status, PoseidonStatus::NullPointer);
+        let status = unsafe { poseidon_hash([0xff; 32].as_ptr(), 32, out.as_mut_ptr()) };
+        assert_eq!(status, PoseidonStatus::NonCanonical);
+        let status = unsafe { poseidon_hash([0u8; 17 * 32].as_ptr(), 17 * 32, out.as_mut_ptr()) };
+        assert_eq!(status, PoseidonStatus::InputTooLong);
+
+        let msg = b"Lorem ipsum dolor sit amet";
+        let status = unsafe { poseidon_hash_bytes(msg.as_ptr(), msg.len(), 16, out.as_mut_ptr()) };
+        assert_eq!(status, PoseidonStatus::Ok);
+        assert_eq!(out, Poseidon::new().hash_bytes(msg).unwrap().to_bytes_le());
+        let status = unsafe { poseidon_sponge_hash(inp.as_ptr(), inp.len(), 1, out.as_mut_ptr()) };
+        assert_eq!(status, PoseidonStatus::UnsupportedFrameSize);
+    }
+
+    #[test]
+    fn test_status_message() {
+        let msg = |status| unsafe { CStr::from_ptr(poseidon_status_message(status)) };
+        assert_eq!(msg(PoseidonStatus::Ok as u32).to_str(), Ok("Ok"));
+        assert_eq!(
+            msg(PoseidonStatus::NonCanonical as u32).to_str(),
+            Ok("Non canonical field element")
+        );
+        assert_eq!(msg(PoseidonStatus::Error as u32).to_str(), Ok("Error"));
+        assert_eq!(msg(7).to_str(), Ok("Unknown status"));
+        assert_eq!(msg(u32::MAX).to_str(), Ok("Unknown status"));
+    }
+}
@@ -0,0 +1,373 @@
+// Field multiplication used by the permutation. With the asm feature, on
+// Linux x86-64 (with BMI2 and ADX, checked at runtime) and aarch64, the
+// Montgomery multiplication can be done in assembly over the raw limbs of
+// Fr, otherwise it uses the Field implementation of Fr (ff_ce, or fr.rs
+// without std). The backend is selected once, when a hasher is created, and
+// the permutation is monomorphized over its multiplication, so there is no
+// dispatch left in the hot loop.
+//
+// Both implementations use CIOS Montgomery multiplication with R = 2^256,
+// the same representation used by Fr, and skip the final carry word, which
+// is fine as the most significant limb of the modulus is smaller than
+// (2^64 - 1) / 2 - 1.
+use crate::fr::*;
+
+use crate::Fr;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FieldBackend {
+    Ff,
+    Asm,
+}
+
+impl FieldBackend {
+    pub fn detect() -> FieldBackend {
+        if FieldBackend::Asm.is_supported() {
+            FieldBackend::Asm
+        } else {
+            FieldBackend::Ff
+        }
+    }
+
+    pub fn is_supported(&self) -> bool {
+        match self {
+            FieldBackend::Ff => true,
+            FieldBackend::Asm => backend::is_supported(),
+        }
+    }
+}
+
+pub(crate) trait Mul {
+    fn mul_assign(a: &mut Fr, b: &Fr);
+    fn square(a: &mut Fr);
+}
+
+pub(crate) struct FfMul;
+
+impl Mul for FfMul {
+    #[inline(always)]
+    fn mul_assign(a: &mut Fr, b: &Fr) {
+        Field::mul_assign(a, b);
+    }
+
+    #[inline(always)]
+    fn square(a: &mut Fr) {
+        Field::square(a);
+    }
+}
+
+// only used once FieldBackend::Asm has been checked to be supported
+#[cfg(all(
+    feature = "asm",
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+pub(crate) struct AsmMul;
+
+#[cfg(all(
+    feature = "asm",
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+impl Mul for AsmMul {
+    #[inline(always)]
+    fn mul_assign(a: &mut Fr, b: &Fr) {
+        (a.0).0 = backend::mul(&(a.0).0, &(b.0).0);
+    }
+
+    #[inline(always)]
+    fn square(a: &mut Fr) {
+        (a.0).0 = backend::mul(&(a.0).0, &(a.0).0);
+    }
+}
+
+// multiplication with the given backend, for the code outside of the
+// permutation
+#[cfg(feature = "alloc")]
+pub(crate) fn mul_assign(backend: FieldBackend, a: &mut Fr, b: &Fr) {
+    match backend {
+        #[cfg(all(
+            feature = "asm",
+            target_os = "linux",
+            any(target_arch = "x86_64", target_arch = "aarch64")
+        ))]
+        FieldBackend::Asm => AsmMul::mul_assign(a, b),
+        _ => FfMul::mul_assign(a, b),
+    }
+}
+
+// BN254 scalar field modulus, followed by -modulus^-1 mod 2^64
+#[cfg(all(
+    feature = "asm",
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+static MODULUS: [u64; 5] = [
+    0x43e1f593f0000001,
+    0x2833e84879b97091,
+    0xb85045b68181585d,
+    0x30644e72e131a029,
+    0xc2e1f593efffffff,
+];
+
+#[cfg(all(feature = "asm", target_os = "linux", target_arch = "x86_64"))]
+mod backend {
+    use super::MODULUS;
+    use core::arch::asm;
+
+    pub fn is_supported() -> bool {
+        is_x86_feature_detected!("bmi2") && is_x86_feature_detected!("adx")
+    }
+
+    #[inline(always)]
+    pub fn mul(a: &[u64; 4], b: &[u64; 4]) -> [u64; 4] {
+        // safe as it is only called through AsmMul, which is only used
+        // once the CPU features have been checked
+        unsafe { mont_mul(a, b) }
+    }
+
+    // One iteration multiplies a by b[i] using the CF (adcx) and OF (adox)
+    // carry chains in parallel, then adds m * modulus and shifts by one limb.
+    macro_rules! iteration {
+        ($i:literal) => {
+            concat!(
+                "mov rdx, qword ptr [{b} + 8*",
+                $i,
+                "]\n",
+                "xor eax, eax\n",
+                "mulx {hi}, rax, qword ptr [{a}]\n",
+                "adox {t0}, rax\n",
+                "adcx {t1}, {hi}\n",
+                "mulx {hi}, rax, qword ptr [{a} + 8]\n",
+                "adox {t1}, rax\n",
+                "adcx {t2}, {hi}\n",
+                "mulx {hi}, rax, qword ptr [{a} + 16]\n",
+                "adox {t2}, rax\n",
+                "adcx {t3}, {hi}\n",
+                "mulx {t4}, rax, qword ptr [{a} + 24]\n",
+                "adox {t3}, rax\n",
+                "mov eax, 0\n",
+                "adcx {t4}, rax\n",
+                "adox {t4}, rax\n",
+                "mov rdx, {t0}\n",
+                "imul rdx, qword ptr [{p} + 32]\n",
+                "xor eax, eax\n",
+                "mulx {hi}, rax, qword ptr [{p}]\n",
+                "adcx rax, {t0}\n",
+                "mov {t0}, {hi}\n",
+                "adcx {t0}, {t1}\n",
+                "mulx {t1}, rax, qword ptr [{p} + 8]\n",
+                "adox {t0}, rax\n",
+                "adcx {t1}, {t2}\n",
+                "mulx {t2}, rax, qword ptr [{p} + 16]\n",
+                "adox {t1}, rax\n",
+                "adcx {t2}, {t3}\n",
+                "mulx {t3}, rax, qword ptr [{p} + 24]\n",
+                "adox {t2}, rax\n",
+                "mov eax, 0\n",
+                "adcx {t3}, rax\n",
+                "adox {t3}, {t4}\n",
+            )
+        };
+    }
+
+    #[target_feature(enable = "bmi2,adx")]
+    unsafe fn mont_mul(a: &[u64; 4], b: &[u64; 4]) -> [u64; 4] {
+        let mut r = [0u64; 4];
+        asm!(
+            iteration!(0),
+            iteration!(1),
+            iteration!(2),
+            iteration!(3),
+            // subtract the modulus if the result is not reduced
+            "mov rax, {t0}",
+            "sub rax, qword ptr [{p}]",
+            "mov rdx, {t1}",
+            "sbb rdx, qword ptr [{p} + 8]",
+            "mov {hi}, {t2}",
+            "sbb {hi}, qword ptr [{p} + 16]",
+            "mov {t4}, {t3}",
+            "sbb {t4}, qword ptr [{p} + 24]",
+            "cmovnc {t0}, rax",
+            "cmovnc {t1}, rdx",
+            "cmovnc {t2}, {hi}",
+            "cmovnc {t3}, {t4}",
+            a = in(reg) a.as_ptr(),
+            b = in(reg) b.as_ptr(),
+            p = in(reg) MODULUS.as_ptr(),
+            t0 = inout(reg) 0u64 => r[0],
+            t1 = inout(reg) 0u64 => r[1],
+            t2 = inout(reg) 0u64 => r[2],
+            t3 = inout(reg) 0u64 => r[3],
+            t4 = out(reg) _,
+            hi = out(reg) _,
+            out("rax") _,
+            out("rdx") _,
+            options(pure, readonly, nostack),
+        );
+        r
+    }
+}
+
+#[cfg(all(feature = "asm", target_os = "linux", target_arch = "aarch64"))]
+mod backend {
+    use super::MODULUS;
+    use core::arch::asm;
+
+    // Each iteration adds the low and the high halves of a * b[i] with two
+    // carry chains, then adds m * modulus and shifts by one limb.
+    macro_rules! iteration {
+        () => {
+            concat!(
+                "ldr {bi}, [{b}], #8\n",
+                "mul {u0}, {a0}, {bi}\n",
+                "mul {u1}, {a1}, {bi}\n",
+                "mul {u2}, {a2}, {bi}\n",
+                "mul {u3}, {a3}, {bi}\n",
+                "adds {t0}, {t0}, {u0}\n",
+                "adcs {t1}, {t1}, {u1}\n",
+                "adcs {t2}, {t2}, {u2}\n",
+                "adcs {t3}, {t3}, {u3}\n",
+                "adc {t4}, xzr, xzr\n",
+                "umulh {u0}, {a0}, {bi}\n",
+                "umulh {u1}, {a1}, {bi}\n",
+                "umulh {u2}, {a2}, {bi}\n",
+                "umulh {u3}, {a3}, {bi}\n",
+                "adds {t1}, {t1}, {u0}\n",
+                "adcs {t2}, {t2}, {u1}\n",
+                "adcs {t3}, {t3}, {u2}\n",
+                "adc {t4}, {t4}, {u3}\n",
+                "mul {bi}, {t0}, {inv}\n",
+                "mul {u0}, {bi}, {p0}\n",
+                "mul {u1}, {bi}, {p1}\n",
+                "mul {u2}, {bi}, {p2}\n",
+                "mul {u3}, {bi}, {p3}\n",
+                "cmn {t0}, {u0}\n",
+                "adcs {t1}, {t1}, {u1}\n",
+                "adcs {t2}, {t2}, {u2}\n",
+                "adcs {t3}, {t3}, {u3}\n",
+                "adc {t4}, {t4}, xzr\n",
+                "umulh {u0}, {bi}, {p0}\n",
+                "umulh {u1}, {bi}, {p1}\n",
+                "umulh {u2}, {bi}, {p2}\n",
+                "umulh {u3}, {bi}, {p3}\n",
+                "adds {t0}, {t1}, {u0}\n",
+                "adcs {t1}, {t2}, {u1}\n",
+                "adcs {t2}, {t3}, {u2}\n",
+                "adc {t3}, {t4}, {u3}\n",
+            )
+        };
+    }
+
+    pub fn is_supported() -> bool {
+        true
+    }
+
+    #[inline(always)]
+    pub fn mul(a: &[u64; 4], b: &[u64; 4]) -> [u64; 4] {
+        let mut r = [0u64; 4];
+        unsafe {
+            asm!(
+                iteration!(),
+                iteration!(),
+                iteration!(),
+                iteration!(),
+                // subtract the modulus if the result is not reduced
+                "subs {u0}, {t0}, {p0}",
+                "sbcs {u1}, {t1}, {p1}",
+                "sbcs {u2}, {t2}, {p2}",
+                "sbcs {u3}, {t3}, {p3}",
+                "csel {t0}, {u0}, {t0}, hs",
+                "csel {t1}, {u1}, {t1}, hs",
+                "csel {t2}, {u2}, {t2}, hs",
+                "csel {t3}, {u3}, {t3}, hs",
+                a0 = in(reg) a[0],
+                a1 = in(reg) a[1],
+                a2 = in(reg) a[2],
+                a3 = in(reg) a[3],
+                b = inout(reg) b.as_ptr() => _,
+                p0 = in(reg) MODULUS[0],
+                p1 = in(reg) MODULUS[1],
+                p2 = in(reg) MODULUS[2],
+                p3 = in(reg) MODULUS[3],
+                inv = in(reg) MODULUS[4],
+                t0 = inout(reg) 0u64 => r[0],
+                t1 = inout(reg) 0u64 => r[1],
+                t2 = inout(reg) 0u64 => r[2],
+                t3 = inout(reg) 0u64 => r[3],
+                t4 = out(reg) _,
+                bi = out(reg) _,
+                u0 = out(reg) _,
+                u1 = out(reg) _,
+                u2 = out(reg) _,
+                u3 = out(reg) _,
+                options(pure, readonly, nostack),
+            );
+        }
+        r
+    }
+}
+
+#[cfg(not(all(
+    feature = "asm",
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+)))]
+mod backend {
+    pub fn is_supported() -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FrRepr;
+
+    fn check<M: Mul>(values: &[Fr]) {
+        for (i, a) in values.iter().enumerate() {
+            let b = values[(i * 7 + 3) % values.len()];
+            let mut expected = *a;
+            expected.mul_assign(&b);
+            let mut r = *a;
+            M::mul_assign(&mut r, &b);
+            assert_eq!(r, expected);
+
+            let mut expected = *a;
+            expected.square();
+            let mut r = *a;
+            M::square(&mut r);
+            assert_eq!(r, expected);
+        }
+    }
+
+    #[test]
+    fn test_mul() {
+        let mut p_minus_1 = Fr::one();
+        p_minus_1.negate();
+        let mut values = vec![
+            Fr::zero(),
+            Fr::one(),
+            p_minus_1,
+            Fr::from_repr(FrRepr::from(u64::MAX)).unwrap(),
+        ];
+        // pseudorandom elements, x -> x^2 + 7
+        let mut x =
+            Fr::from_repr(FrRepr([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654])).unwrap();
+        for _ in 0..1000 {
+            x.square();
+            x.add_assign(&Fr::from_repr(FrRepr::from(7)).unwrap());
+            values.push(x);
+        }
+        check::<FfMul>(&values);
+        #[cfg(all(
+            feature = "asm",
+            target_os = "linux",
+            any(target_arch = "x86_64", target_arch = "aarch64")
+        ))]
+        if FieldBackend::Asm.is_supported() {
+            check::<AsmMul>(&values);
+        }
+        assert!(FieldBackend::detect().is_supported());
+    }
+}
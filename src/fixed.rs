@@ -0,0 +1,150 @@
+// Fixed-width Poseidon, with the width T (number of inputs + 1) known at
+// compile time. The permutation works in place over [Fr; T] and never
+// allocates. The constants are borrowed from static tables generated by
+// build.rs, so an instance is only a couple of slices.
+use crate::fr::*;
+
+use crate::field::FieldBackend;
+use crate::permutation;
+use crate::{Fr, FrRepr, PoseidonError, N_ROUNDS_F, N_ROUNDS_P};
+
+// round constants and MDS matrices of each width, in Montgomery form
+mod tables {
+    use super::{Fr, FrRepr};
+
+    include!(concat!(env!("OUT_DIR"), "/tables.rs"));
+}
+
+#[derive(Clone, Copy)]
+pub struct Poseidon<const T: usize> {
+    // (n_rounds_f + n_rounds_p) * T round constants
+    pub(crate) c: &'static [Fr],
+    // T * T matrix, row by row
+    pub(crate) m: &'static [Fr],
+    pub(crate) n_rounds_f: usize,
+    pub(crate) n_rounds_p: usize,
+    backend: FieldBackend,
+}
+
+impl<const T: usize> Poseidon<T> {
+    pub fn new() -> Result<Poseidon<T>, PoseidonError> {
+        if T < 2 || T > N_ROUNDS_P.len() + 1 {
+            return Err(PoseidonError::UnsupportedWidth(T));
+        }
+        let n_rounds_p = N_ROUNDS_P[T - 2];
+        let c = tables::C[T - 2];
+        let m = tables::M[T - 2];
+        if c.len() != (N_ROUNDS_F + n_rounds_p) * T || m.len() != T * T {
+            return Err(PoseidonError::MalformedConstants(
+                "Wrong number of constants",
+            ));
+        }
+        Ok(Poseidon {
+            c,
+            m,
+            n_rounds_f: N_ROUNDS_F,
+            n_rounds_p,
+            backend: FieldBackend::detect(),
+        })
+    }
+
+    pub fn backend(&self) -> FieldBackend {
+        self.backend
+    }
+
+    pub fn with_backend(mut self, backend: FieldBackend) -> Result<Poseidon<T>, PoseidonError> {
+        if !backend.is_supported() {
+            return Err(PoseidonError::UnsupportedBackend);
+        }
+        self.backend = backend;
+        Ok(self)
+    }
+
+    pub fn permute(&self, state: &mut [Fr; T]) {
+        let mut scratch = [Fr::zero(); T];
+        permutation::permute_fr(
+            self.backend,
+            state,
+            &mut scratch,
+            self.c,
+            self.m,
+            self.n_rounds_f,
+            self.n_rounds_p,
+        );
+    }
+
+    // same output as crate::Poseidon::hash for T - 1 inputs
+    pub fn hash(&self, inp: &[Fr]) -> Result<Fr, PoseidonError> {
+        if inp.len() != T - 1 {
+            return Err(PoseidonError::WrongInputsLength { expected: T - 1 });
+        }
+        let mut state = [Fr::zero(); T];
+        state[1..].copy_from_slice(inp);
+        self.permute(&mut state);
+        Ok(state[0])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash() {
+        let p3 = Poseidon::<3>::new().unwrap();
+        let h = p3.hash(&[Fr::from_u64(1), Fr::from_u64(2)]).unwrap();
+        assert_eq!(
+            h.to_string(),
+            "Fr(0x115cc0f5e7d690413df64c6b9662e9cf2a3617f2743245519e19607a4417189a)"
+        );
+
+        let p17 = Poseidon::<17>::new().unwrap();
+        assert_eq!(
+            p3.hash(&[Fr::from_u64(1)]),
+            Err(PoseidonError::WrongInputsLength { expected: 2 })
+        );
+        assert_eq!(
+            Poseidon::<1>::new().err(),
+            Some(PoseidonError::UnsupportedWidth(1))
+        );
+        assert!(Poseidon::<18>::new().is_err());
+
+        // the constants are borrowed, not copied
+        assert!(core::mem::size_of::<Poseidon<17>>() <= 64);
+        assert!(core::ptr::eq(p17.c, Poseidon::<17>::new().unwrap().c));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_same_as_poseidon() {
+        let poseidon = crate::Poseidon::new();
+
+        let p2 = Poseidon::<2>::new().unwrap();
+        assert_eq!(
+            p2.hash(&[Fr::from_u64(7)]).unwrap(),
+            poseidon.hash(vec![Fr::from_u64(7)]).unwrap()
+        );
+
+        let p17 = Poseidon::<17>::new().unwrap();
+        let inp: Vec<Fr> = (1..17).map(Fr::from_u64).collect();
+        assert_eq!(p17.hash(&inp).unwrap(), poseidon.hash(inp).unwrap());
+    }
+
+    #[test]
+    fn test_backends() {
+        let p3 = Poseidon::<3>::new().unwrap();
+        assert!(p3.backend().is_supported());
+        let ff = p3.with_backend(FieldBackend::Ff).unwrap();
+        let expected = ff.hash(&[Fr::from_u64(1), Fr::from_u64(2)]).unwrap();
+        match p3.with_backend(FieldBackend::Asm) {
+            Ok(asm) => assert_eq!(
+                asm.hash(&[Fr::from_u64(1), Fr::from_u64(2)]).unwrap(),
+                expected
+            ),
+            Err(e) => {
+                assert!(!FieldBackend::Asm.is_supported());
+                assert_eq!(e, PoseidonError::UnsupportedBackend);
+            }
+        }
+    }
+}
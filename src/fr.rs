@@ -0,0 +1,420 @@
+// Field traits implemented by Fr. With std, Fr is derived by ff_ce and these
+// are its traits. ff_ce needs std (and rand), so without std Fr is instead
+// implemented here with core only: the same Montgomery representation
+// (R = 2^256) and the subset of the ff_ce API used by this crate, under the
+// same names, so the rest of the crate is written once for both.
+#[cfg(feature = "std")]
+pub use ff::{to_hex, Field, PrimeField, PrimeFieldDecodingError, PrimeFieldRepr};
+
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+pub use self::core_field::to_hex;
+#[cfg(not(feature = "std"))]
+pub use self::core_field::{Field, PrimeField, PrimeFieldDecodingError, PrimeFieldRepr};
+
+#[cfg(not(feature = "std"))]
+mod core_field {
+    use core::cmp::Ordering;
+    use core::fmt;
+
+    use crate::{Fr, FrRepr};
+
+    include!("mont.rs");
+
+    // 2^256 mod modulus
+    const R: [u64; 4] = [
+        0xac96341c4ffffffb,
+        0x36fc76959f60cd29,
+        0x666ea36f7879462e,
+        0x0e0a77c19a07df2f,
+    ];
+
+    pub trait Field: Sized + Eq + Copy + Clone + fmt::Debug + fmt::Display + 'static {
+        fn zero() -> Self;
+        fn one() -> Self;
+        fn is_zero(&self) -> bool;
+        fn square(&mut self);
+        fn double(&mut self);
+        fn negate(&mut self);
+        fn add_assign(&mut self, other: &Self);
+        fn sub_assign(&mut self, other: &Self);
+        fn mul_assign(&mut self, other: &Self);
+        fn inverse(&self) -> Option<Self>;
+
+        // self^exp, with exp given as little-endian limbs
+        fn pow<S: AsRef<[u64]>>(&self, exp: S) -> Self {
+            let mut res = Self::one();
+            for limb in exp.as_ref().iter().rev() {
+                for i in (0..64).rev() {
+                    res.square();
+                    if (limb >> i) & 1 == 1 {
+                        res.mul_assign(self);
+                    }
+                }
+            }
+            res
+        }
+    }
+
+    pub trait PrimeFieldRepr:
+        Sized
+        + Copy
+        + Clone
+        + Eq
+        + Ord
+        + Default
+        + fmt::Debug
+        + fmt::Display
+        + AsRef<[u64]>
+        + AsMut<[u64]>
+        + From<u64>
+        + 'static
+    {
+        fn is_zero(&self) -> bool;
+        fn is_odd(&self) -> bool;
+        fn is_even(&self) -> bool;
+        fn num_bits(&self) -> u32;
+        // self -= other, ignoring the borrow
+        fn sub_noborrow(&mut self, other: &Self);
+        // self += other, ignoring the carry
+        fn add_nocarry(&mut self, other: &Self);
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub enum PrimeFieldDecodingError {
+        // the representation is not smaller than the modulus
+        NotInField,
+    }
+
+    impl fmt::Display for PrimeFieldDecodingError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                PrimeFieldDecodingError::NotInField => write!(f, "not an element of the field"),
+            }
+        }
+    }
+
+    // into_* take &self as in ff
+    #[allow(clippy::wrong_self_convention)]
+    pub trait PrimeField: Field {
+        type Repr: PrimeFieldRepr + From<Self>;
+
+        const NUM_BITS: u32;
+        const CAPACITY: u32;
+
+        fn from_repr(r: Self::Repr) -> Result<Self, PrimeFieldDecodingError>;
+        // from the Montgomery form
+        fn from_raw_repr(r: Self::Repr) -> Result<Self, PrimeFieldDecodingError>;
+        fn into_repr(&self) -> Self::Repr;
+        // into the Montgomery form
+        fn into_raw_repr(&self) -> Self::Repr;
+        fn char() -> Self::Repr;
+
+        // decimal string, without leading zeros, reduced modulo the modulus
+        // as in ff
+        fn from_str(s: &str) -> Option<Self> {
+            if s.is_empty() {
+                return None;
+            }
+            if s == "0" {
+                return Some(Self::zero());
+            }
+            let mut res = Self::zero();
+            let ten = Self::from_repr(Self::Repr::from(10)).unwrap();
+            let mut first_digit = true;
+            for c in s.chars() {
+                let c = c.to_digit(10)?;
+                if first_digit {
+                    if c == 0 {
+                        return None;
+                    }
+                    first_digit = false;
+                }
+                res.mul_assign(&ten);
+                res.add_assign(&Self::from_repr(Self::Repr::from(u64::from(c))).unwrap());
+            }
+            Some(res)
+        }
+    }
+
+    // big-endian hex digits of el, without prefix
+    #[cfg(feature = "alloc")]
+    pub fn to_hex<F: PrimeField>(el: &F) -> alloc::string::String {
+        use core::fmt::Write;
+
+        let mut s = alloc::string::String::new();
+        for limb in el.into_repr().as_ref().iter().rev() {
+            write!(s, "{:016x}", limb).unwrap();
+        }
+        s
+    }
+
+    // a + b, returning the carry
+    fn add(a: &mut [u64; 4], b: &[u64; 4]) -> bool {
+        let mut carry = false;
+        for (a, b) in a.iter_mut().zip(b) {
+            let (s, c1) = a.overflowing_add(*b);
+            let (s, c2) = s.overflowing_add(carry as u64);
+            *a = s;
+            carry = c1 || c2;
+        }
+        carry
+    }
+
+    impl fmt::Debug for FrRepr {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            fmt::Display::fmt(self, f)
+        }
+    }
+
+    impl fmt::Display for FrRepr {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "0x")?;
+            for limb in self.0.iter().rev() {
+                write!(f, "{:016x}", limb)?;
+            }
+            Ok(())
+        }
+    }
+
+    impl AsRef<[u64]> for FrRepr {
+        fn as_ref(&self) -> &[u64] {
+            &self.0
+        }
+    }
+
+    impl AsMut<[u64]> for FrRepr {
+        fn as_mut(&mut self) -> &mut [u64] {
+            &mut self.0
+        }
+    }
+
+    impl From<u64> for FrRepr {
+        fn from(val: u64) -> FrRepr {
+            FrRepr([val, 0, 0, 0])
+        }
+    }
+
+    impl From<Fr> for FrRepr {
+        fn from(e: Fr) -> FrRepr {
+            e.into_repr()
+        }
+    }
+
+    impl Ord for FrRepr {
+        fn cmp(&self, other: &FrRepr) -> Ordering {
+            self.0.iter().rev().cmp(other.0.iter().rev())
+        }
+    }
+
+    impl PartialOrd for FrRepr {
+        fn partial_cmp(&self, other: &FrRepr) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl PrimeFieldRepr for FrRepr {
+        fn is_zero(&self) -> bool {
+            self.0 == [0; 4]
+        }
+
+        fn is_odd(&self) -> bool {
+            self.0[0] & 1 == 1
+        }
+
+        fn is_even(&self) -> bool {
+            !self.is_odd()
+        }
+
+        fn num_bits(&self) -> u32 {
+            let mut bits = 256;
+            for limb in self.0.iter().rev() {
+                bits -= limb.leading_zeros();
+                if *limb != 0 {
+                    break;
+                }
+            }
+            bits
+        }
+
+        fn sub_noborrow(&mut self, other: &FrRepr) {
+            sub(&mut self.0, &other.0);
+        }
+
+        fn add_nocarry(&mut self, other: &FrRepr) {
+            add(&mut self.0, &other.0);
+        }
+    }
+
+    impl fmt::Debug for Fr {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "Fr({:?})", self.into_repr())
+        }
+    }
+
+    impl fmt::Display for Fr {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "Fr({})", self.into_repr())
+        }
+    }
+
+    impl Field for Fr {
+        fn zero() -> Fr {
+            Fr(FrRepr([0; 4]))
+        }
+
+        fn one() -> Fr {
+            Fr(FrRepr(R))
+        }
+
+        fn is_zero(&self) -> bool {
+            self.0.is_zero()
+        }
+
+        fn square(&mut self) {
+            self.0 .0 = mont_mul(&self.0 .0, &self.0 .0);
+        }
+
+        fn double(&mut self) {
+            let s = *self;
+            self.add_assign(&s);
+        }
+
+        fn negate(&mut self) {
+            if !self.is_zero() {
+                let mut r = MODULUS;
+                sub(&mut r, &self.0 .0);
+                self.0 .0 = r;
+            }
+        }
+
+        fn add_assign(&mut self, other: &Fr) {
+            // both are below the modulus, which is smaller than 2^255, so
+            // the sum does not overflow
+            add(&mut self.0 .0, &other.0 .0);
+            if !lt_modulus(&self.0 .0) {
+                sub(&mut self.0 .0, &MODULUS);
+            }
+        }
+
+        fn sub_assign(&mut self, other: &Fr) {
+            if sub(&mut self.0 .0, &other.0 .0) {
+                add(&mut self.0 .0, &MODULUS);
+            }
+        }
+
+        fn mul_assign(&mut self, other: &Fr) {
+            self.0 .0 = mont_mul(&self.0 .0, &other.0 .0);
+        }
+
+        fn inverse(&self) -> Option<Fr> {
+            if self.is_zero() {
+                return None;
+            }
+            // Fermat's little theorem, self^(modulus - 2)
+            let mut exp = MODULUS;
+            sub(&mut exp, &[2, 0, 0, 0]);
+            Some(self.pow(exp))
+        }
+    }
+
+    impl PrimeField for Fr {
+        type Repr = FrRepr;
+
+        const NUM_BITS: u32 = 254;
+        const CAPACITY: u32 = 253;
+
+        fn from_repr(r: FrRepr) -> Result<Fr, PrimeFieldDecodingError> {
+            if !lt_modulus(&r.0) {
+                return Err(PrimeFieldDecodingError::NotInField);
+            }
+            Ok(Fr(FrRepr(mont_mul(&r.0, &R2))))
+        }
+
+        fn from_raw_repr(r: FrRepr) -> Result<Fr, PrimeFieldDecodingError> {
+            if !lt_modulus(&r.0) {
+                return Err(PrimeFieldDecodingError::NotInField);
+            }
+            Ok(Fr(r))
+        }
+
+        fn into_repr(&self) -> FrRepr {
+            FrRepr(mont_mul(&self.0 .0, &[1, 0, 0, 0]))
+        }
+
+        fn into_raw_repr(&self) -> FrRepr {
+            self.0
+        }
+
+        fn char() -> FrRepr {
+            FrRepr(MODULUS)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_arithmetic() {
+            let mut p_minus_1 = Fr::one();
+            p_minus_1.negate();
+            assert_eq!(
+                p_minus_1.into_repr(),
+                FrRepr([MODULUS[0] - 1, MODULUS[1], MODULUS[2], MODULUS[3]])
+            );
+
+            let mut a = p_minus_1;
+            a.add_assign(&Fr::from_u64(3));
+            assert_eq!(a, Fr::from_u64(2));
+            a.sub_assign(&Fr::from_u64(5));
+            let mut b = Fr::from_u64(3);
+            b.negate();
+            assert_eq!(a, b);
+
+            let mut sq = p_minus_1;
+            sq.square();
+            assert_eq!(sq, Fr::one());
+
+            let mut a = Fr::from_u64(u64::MAX);
+            a.mul_assign(&Fr::from_u64(u64::MAX));
+            assert_eq!(a.into_repr(), FrRepr([1, u64::MAX - 1, 0, 0]));
+
+            let mut inv = Fr::from_u64(7).inverse().unwrap();
+            inv.mul_assign(&Fr::from_u64(7));
+            assert_eq!(inv, Fr::one());
+            assert_eq!(Fr::zero().inverse(), None);
+        }
+
+        #[test]
+        fn test_repr() {
+            assert_eq!(
+                Fr::from_repr(FrRepr(MODULUS)),
+                Err(PrimeFieldDecodingError::NotInField)
+            );
+            assert_eq!(Fr::one().into_raw_repr(), FrRepr(R));
+            assert_eq!(Fr::from_raw_repr(FrRepr(R)).unwrap(), Fr::one());
+            assert_eq!(Fr::char().num_bits(), Fr::NUM_BITS);
+            assert!(FrRepr::from(1) < FrRepr([0, 1, 0, 0]));
+
+            assert_eq!(Fr::from_str("0"), Some(Fr::zero()));
+            assert_eq!(
+                Fr::from_str("18446744073709551615"),
+                Some(Fr::from_u64(u64::MAX))
+            );
+            assert_eq!(Fr::from_str(""), None);
+            assert_eq!(Fr::from_str("01"), None);
+            assert_eq!(Fr::from_str("1a"), None);
+            // reduced modulo the modulus, as in ff
+            assert_eq!(
+                Fr::from_str(
+                    "21888242871839275222246405745257275088548364400416034343698204186575808495618"
+                ),
+                Some(Fr::one())
+            );
+            assert_eq!(
+                Fr::from_u64(255).to_string(),
+                "Fr(0x00000000000000000000000000000000000000000000000000000000000000ff)"
+            );
+        }
+    }
+}
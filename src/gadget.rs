@@ -0,0 +1,393 @@
+// bellman R1CS gadget following the same ark/sbox/mix schedule as
+// Poseidon::hash, for any engine whose scalar field is the BN254 one.
+//
+// ark and mix are linear, so they only update linear combinations and cost
+// no constraints. Each x^5 costs 3 constraints (x2 = x * x, x4 = x2 * x2,
+// x5 = x4 * x), and the output is allocated with one more constraint, so a
+// hash of width t costs 3 * (t * R_F + R_P) + 1 constraints.
+//
+// bellman's LinearCombination never merges terms of the same variable, and
+// the mix of the partial rounds would make it grow exponentially, so the
+// state is kept as Num, which merges them.
+use bellman_ce::pairing::ff::{Field, PrimeField};
+use bellman_ce::pairing::Engine;
+use bellman_ce::{ConstraintSystem, LinearCombination, SynthesisError, Variable};
+
+use crate::convert::Dec;
+use crate::permutation;
+use crate::{load_constants, Fr, PoseidonError};
+
+// p - 1 of BN254's scalar field
+const MODULUS_MINUS_1: &str =
+    "21888242871839275222246405745257275088548364400416034343698204186575808495616";
+
+#[derive(Clone, Debug)]
+pub struct Num<E: Engine> {
+    // each variable appears at most once
+    terms: Vec<(Variable, E::Fr)>,
+    value: Option<E::Fr>,
+}
+
+impl<E: Engine> Num<E> {
+    pub fn zero() -> Num<E> {
+        Num {
+            terms: Vec::new(),
+            value: Some(E::Fr::zero()),
+        }
+    }
+
+    pub fn from_variable(var: Variable, value: Option<E::Fr>) -> Num<E> {
+        Num {
+            terms: vec![(var, E::Fr::one())],
+            value,
+        }
+    }
+
+    pub fn alloc<CS: ConstraintSystem<E>>(
+        mut cs: CS,
+        value: Option<E::Fr>,
+    ) -> Result<Num<E>, SynthesisError> {
+        let var = cs.alloc(|| "num", || value.ok_or(SynthesisError::AssignmentMissing))?;
+        Ok(Num::from_variable(var, value))
+    }
+
+    pub fn value(&self) -> Option<E::Fr> {
+        self.value
+    }
+
+    pub fn lc(&self) -> LinearCombination<E> {
+        self.terms
+            .iter()
+            .fold(LinearCombination::zero(), |lc, (var, coeff)| {
+                lc + (*coeff, *var)
+            })
+    }
+
+    fn add_term(&mut self, var: Variable, coeff: E::Fr) {
+        let index = var.get_unchecked();
+        match self
+            .terms
+            .iter_mut()
+            .find(|(v, _)| v.get_unchecked() == index)
+        {
+            Some((_, c)) => c.add_assign(&coeff),
+            None => self.terms.push((var, coeff)),
+        }
+    }
+
+    fn add_constant<CS: ConstraintSystem<E>>(&mut self, c: &E::Fr) {
+        self.add_term(CS::one(), *c);
+        if let Some(v) = self.value.as_mut() {
+            v.add_assign(c);
+        }
+    }
+
+    // self += k * other
+    fn add_scaled(&mut self, other: &Num<E>, k: &E::Fr) {
+        for (var, coeff) in &other.terms {
+            let mut c = *coeff;
+            c.mul_assign(k);
+            self.add_term(*var, c);
+        }
+        self.value = match (self.value, other.value) {
+            (Some(mut a), Some(mut b)) => {
+                b.mul_assign(k);
+                a.add_assign(&b);
+                Some(a)
+            }
+            _ => None,
+        };
+    }
+}
+
+fn to_engine<E: Engine>(e: &Fr) -> Result<E::Fr, PoseidonError> {
+    E::Fr::from_str(&Dec(*e).to_string()).ok_or(PoseidonError::MalformedConstants(
+        "Engine scalar field is not the BN254 one",
+    ))
+}
+
+pub struct PoseidonGadget<E: Engine> {
+    n_rounds_f: usize,
+    n_rounds_p: Vec<usize>,
+    c: Vec<Vec<E::Fr>>,
+    m: Vec<Vec<Vec<E::Fr>>>,
+}
+
+impl<E: Engine> PoseidonGadget<E> {
+    pub fn new() -> Result<PoseidonGadget<E>, PoseidonError> {
+        // p - 1 + 1 == 0 only if both fields have the same modulus
+        let mut p = E::Fr::from_str(MODULUS_MINUS_1).ok_or(PoseidonError::MalformedConstants(
+            "Engine scalar field is not the BN254 one",
+        ))?;
+        p.add_assign(&E::Fr::one());
+        if !p.is_zero() {
+            return Err(PoseidonError::MalformedConstants(
+                "Engine scalar field is not the BN254 one",
+            ));
+        }
+
+        let constants = load_constants()?;
+        let convert = |v: &Vec<Fr>| -> Result<Vec<E::Fr>, PoseidonError> {
+            v.iter().map(to_engine::<E>).collect()
+        };
+        Ok(PoseidonGadget {
+            n_rounds_f: constants.n_rounds_f,
+            n_rounds_p: constants.n_rounds_p.clone(),
+            c: constants.c.iter().map(convert).collect::<Result<_, _>>()?,
+            m: constants
+                .m
+                .iter()
+                .map(|m| m.iter().map(convert).collect())
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    // x^5 with 3 constraints
+    fn sbox<CS: ConstraintSystem<E>>(mut cs: CS, x: &Num<E>) -> Result<Num<E>, SynthesisError> {
+        let x2_value = x.value.map(|mut v| {
+            v.square();
+            v
+        });
+        let x2 = cs.alloc(
+            || "x2",
+            || x2_value.ok_or(SynthesisError::AssignmentMissing),
+        )?;
+        cs.enforce(
+            || "x2 = x * x",
+            |lc| lc + &x.lc(),
+            |lc| lc + &x.lc(),
+            |lc| lc + x2,
+        );
+
+        let x4_value = x2_value.map(|mut v| {
+            v.square();
+            v
+        });
+        let x4 = cs.alloc(
+            || "x4",
+            || x4_value.ok_or(SynthesisError::AssignmentMissing),
+        )?;
+        cs.enforce(|| "x4 = x2 * x2", |lc| lc + x2, |lc| lc + x2, |lc| lc + x4);
+
+        let x5_value = match (x4_value, x.value) {
+            (Some(mut a), Some(b)) => {
+                a.mul_assign(&b);
+                Some(a)
+            }
+            _ => None,
+        };
+        let x5 = cs.alloc(
+            || "x5",
+            || x5_value.ok_or(SynthesisError::AssignmentMissing),
+        )?;
+        cs.enforce(
+            || "x5 = x4 * x",
+            |lc| lc + x4,
+            |lc| lc + &x.lc(),
+            |lc| lc + x5,
+        );
+
+        Ok(Num::from_variable(x5, x5_value))
+    }
+
+    // constrains the permutation of width state.len()
+    pub fn permutation<CS: ConstraintSystem<E>>(
+        &self,
+        mut cs: CS,
+        state: &[Num<E>],
+    ) -> Result<Vec<Num<E>>, SynthesisError> {
+        let t = state.len();
+        if t < 2 || t > self.n_rounds_p.len() + 1 {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        let n_rounds_f = self.n_rounds_f;
+        let n_rounds_p = self.n_rounds_p[t - 2];
+        let c = &self.c[t - 2];
+        let m = &self.m[t - 2];
+
+        let mut state = state.to_vec();
+        for i in 0..(n_rounds_f + n_rounds_p) {
+            let mut cs = cs.namespace(|| format!("round {}", i));
+            for (j, s) in state.iter_mut().enumerate() {
+                s.add_constant::<CS>(&c[i * t + j]);
+            }
+            let full = permutation::is_full_round(i, n_rounds_f, n_rounds_p);
+            let n_sbox = if full { t } else { 1 };
+            for (j, s) in state.iter_mut().enumerate().take(n_sbox) {
+                *s = Self::sbox(cs.namespace(|| format!("sbox {}", j)), s)?;
+            }
+            state = m
+                .iter()
+                .map(|row| {
+                    let mut n = Num::zero();
+                    for (mij, s) in row.iter().zip(&state) {
+                        n.add_scaled(s, mij);
+                    }
+                    n
+                })
+                .collect();
+        }
+        Ok(state)
+    }
+
+    // constrains Poseidon::hash(inputs), returning the allocated output
+    pub fn hash<CS: ConstraintSystem<E>>(
+        &self,
+        mut cs: CS,
+        inputs: &[Num<E>],
+    ) -> Result<Num<E>, SynthesisError> {
+        if inputs.is_empty() || inputs.len() > self.n_rounds_p.len() {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        let mut state = vec![Num::zero()];
+        state.extend_from_slice(inputs);
+        let state = self.permutation(cs.namespace(|| "permutation"), &state)?;
+
+        let value = state[0].value;
+        let out = cs.alloc(|| "out", || value.ok_or(SynthesisError::AssignmentMissing))?;
+        cs.enforce(
+            || "out = state[0]",
+            |lc| lc + &state[0].lc(),
+            |lc| lc + CS::one(),
+            |lc| lc + out,
+        );
+        Ok(Num::from_variable(out, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Poseidon;
+    use bellman_ce::pairing::bn256::{Bn256, Fr as BnFr};
+    use bellman_ce::Index;
+
+    // records the assignments and constraints, to check them afterwards
+    struct TestCS {
+        inputs: Vec<BnFr>,
+        aux: Vec<BnFr>,
+        constraints: Vec<(
+            LinearCombination<Bn256>,
+            LinearCombination<Bn256>,
+            LinearCombination<Bn256>,
+        )>,
+    }
+
+    impl TestCS {
+        fn new() -> TestCS {
+            TestCS {
+                inputs: vec![BnFr::one()],
+                aux: Vec::new(),
+                constraints: Vec::new(),
+            }
+        }
+
+        fn eval(&self, lc: &LinearCombination<Bn256>) -> BnFr {
+            let mut r = BnFr::zero();
+            for (var, coeff) in lc.as_ref() {
+                let mut v = match var.get_unchecked() {
+                    Index::Input(i) => self.inputs[i],
+                    Index::Aux(i) => self.aux[i],
+                };
+                v.mul_assign(coeff);
+                r.add_assign(&v);
+            }
+            r
+        }
+
+        fn is_satisfied(&self) -> bool {
+            self.constraints.iter().all(|(a, b, c)| {
+                let mut ab = self.eval(a);
+                ab.mul_assign(&self.eval(b));
+                ab == self.eval(c)
+            })
+        }
+    }
+
+    impl ConstraintSystem<Bn256> for TestCS {
+        type Root = Self;
+
+        fn alloc<F, A, AR>(&mut self, _: A, f: F) -> Result<Variable, SynthesisError>
+        where
+            F: FnOnce() -> Result<BnFr, SynthesisError>,
+            A: FnOnce() -> AR,
+            AR: Into<String>,
+        {
+            self.aux.push(f()?);
+            Ok(Variable::new_unchecked(Index::Aux(self.aux.len() - 1)))
+        }
+
+        fn alloc_input<F, A, AR>(&mut self, _: A, f: F) -> Result<Variable, SynthesisError>
+        where
+            F: FnOnce() -> Result<BnFr, SynthesisError>,
+            A: FnOnce() -> AR,
+            AR: Into<String>,
+        {
+            self.inputs.push(f()?);
+            Ok(Variable::new_unchecked(Index::Input(self.inputs.len() - 1)))
+        }
+
+        fn enforce<A, AR, LA, LB, LC>(&mut self, _: A, a: LA, b: LB, c: LC)
+        where
+            A: FnOnce() -> AR,
+            AR: Into<String>,
+            LA: FnOnce(LinearCombination<Bn256>) -> LinearCombination<Bn256>,
+            LB: FnOnce(LinearCombination<Bn256>) -> LinearCombination<Bn256>,
+            LC: FnOnce(LinearCombination<Bn256>) -> LinearCombination<Bn256>,
+        {
+            self.constraints.push((
+                a(LinearCombination::zero()),
+                b(LinearCombination::zero()),
+                c(LinearCombination::zero()),
+            ));
+        }
+
+        fn push_namespace<NR, N>(&mut self, _: N)
+        where
+            NR: Into<String>,
+            N: FnOnce() -> NR,
+        {
+        }
+
+        fn pop_namespace(&mut self) {}
+
+        fn get_root(&mut self) -> &mut Self::Root {
+            self
+        }
+    }
+
+    #[test]
+    fn test_gadget() {
+        let poseidon = Poseidon::new();
+        let gadget = PoseidonGadget::<Bn256>::new().unwrap();
+
+        for n in [1usize, 2, 16].iter() {
+            let inp: Vec<Fr> = (1..=*n as u64).map(Fr::from_u64).collect();
+            let expected = poseidon.hash(inp.clone()).unwrap();
+
+            let mut cs = TestCS::new();
+            let inputs: Vec<Num<Bn256>> = inp
+                .iter()
+                .enumerate()
+                .map(|(i, e)| {
+                    let v = to_engine::<Bn256>(e).unwrap();
+                    Num::alloc(cs.namespace(|| format!("input {}", i)), Some(v)).unwrap()
+                })
+                .collect();
+            let out = gadget.hash(&mut cs, &inputs).unwrap();
+
+            assert_eq!(out.value(), Some(to_engine::<Bn256>(&expected).unwrap()));
+            assert!(cs.is_satisfied());
+            let t = n + 1;
+            let n_rounds_p = crate::N_ROUNDS_P[t - 2];
+            assert_eq!(cs.constraints.len(), 3 * (t * 8 + n_rounds_p) + 1);
+
+            // a wrong witness does not satisfy the constraints
+            cs.aux[*n + 1].add_assign(&BnFr::one());
+            assert!(!cs.is_satisfied());
+        }
+
+        let mut cs = TestCS::new();
+        assert!(gadget.hash(&mut cs, &[]).is_err());
+    }
+}
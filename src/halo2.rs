@@ -0,0 +1,407 @@
+// halo2 chip following the same ark/sbox/mix schedule as Poseidon::hash,
+// for any field whose modulus is the one of BN254's scalar field, with the
+// circomlib constants, so that the hashes agree with the circom circuits and
+// with the bellman gadget.
+//
+// Each round takes one row: the state is kept in t advice columns and the
+// round constants in t fixed columns, and a gate constrains the next row to
+// be the mix of the sbox of the current row plus the round constants. Full
+// rounds apply x^5 to the whole state and partial rounds only to the first
+// element, so both gates have degree 6, and a hash of width t takes
+// R_F + R_P + 1 rows.
+//
+// halo2_proofs only provides the pasta fields, Bn254Fr (see bn254.rs) is
+// BN254's scalar field for it.
+use halo2_proofs::arithmetic::FieldExt;
+use halo2_proofs::circuit::{AssignedCell, Layouter};
+use halo2_proofs::plonk::{Advice, Column, ConstraintSystem, Error, Expression, Fixed, Selector};
+use halo2_proofs::poly::Rotation;
+
+use crate::convert::Dec;
+use crate::fr::*;
+use crate::permutation;
+use crate::{load_constants, Fr, PoseidonError};
+
+mod bn254;
+
+pub use bn254::Bn254Fr;
+
+pub fn to_field<F: FieldExt>(e: &Fr) -> Result<F, PoseidonError> {
+    F::from_str_vartime(&Dec(*e).to_string()).ok_or(PoseidonError::MalformedConstants(
+        "Field is not BN254's scalar field",
+    ))
+}
+
+#[derive(Clone, Debug)]
+pub struct PoseidonParams<F: FieldExt> {
+    t: usize,
+    n_rounds_f: usize,
+    n_rounds_p: usize,
+    c: Vec<F>,
+    m: Vec<Vec<F>>,
+}
+
+impl<F: FieldExt> PoseidonParams<F> {
+    // the constants of the permutation of width t
+    pub fn new(t: usize) -> Result<PoseidonParams<F>, PoseidonError> {
+        // p - 1 + 1 == 0 only if both fields have the same modulus
+        let mut p_minus_1 = Fr::one();
+        p_minus_1.negate();
+        if to_field::<F>(&p_minus_1)? + F::one() != F::zero() {
+            return Err(PoseidonError::MalformedConstants(
+                "Field is not BN254's scalar field",
+            ));
+        }
+
+        let constants = load_constants()?;
+        if t < 2 || t > constants.n_rounds_p.len() + 1 {
+            return Err(PoseidonError::UnsupportedWidth(t));
+        }
+        let convert = |v: &Vec<Fr>| -> Result<Vec<F>, PoseidonError> {
+            v.iter().map(to_field::<F>).collect()
+        };
+        Ok(PoseidonParams {
+            t,
+            n_rounds_f: constants.n_rounds_f,
+            n_rounds_p: constants.n_rounds_p[t - 2],
+            c: convert(&constants.c[t - 2])?,
+            m: constants.m[t - 2]
+                .iter()
+                .map(convert)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.t
+    }
+
+    pub fn n_rounds(&self) -> usize {
+        self.n_rounds_f + self.n_rounds_p
+    }
+
+    fn is_full_round(&self, i: usize) -> bool {
+        permutation::is_full_round(i, self.n_rounds_f, self.n_rounds_p)
+    }
+
+    // the state after round i
+    fn round(&self, state: &[F], i: usize) -> Vec<F> {
+        let full = self.is_full_round(i);
+        let s: Vec<F> = state
+            .iter()
+            .enumerate()
+            .map(|(j, x)| {
+                let x = *x + self.c[i * self.t + j];
+                if full || j == 0 {
+                    x.square().square() * x
+                } else {
+                    x
+                }
+            })
+            .collect();
+        self.m
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .zip(&s)
+                    .fold(F::zero(), |acc, (m, x)| acc + *m * x)
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PoseidonConfig<F: FieldExt> {
+    params: PoseidonParams<F>,
+    state: Vec<Column<Advice>>,
+    rc: Vec<Column<Fixed>>,
+    s_full: Selector,
+    s_partial: Selector,
+}
+
+pub struct PoseidonChip<F: FieldExt> {
+    config: PoseidonConfig<F>,
+}
+
+impl<F: FieldExt> PoseidonChip<F> {
+    pub fn construct(config: PoseidonConfig<F>) -> PoseidonChip<F> {
+        PoseidonChip { config }
+    }
+
+    // state and rc need params.width() columns each. rc[0] is also used for
+    // the zero capacity element of hash, as a constant.
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        params: PoseidonParams<F>,
+        state: Vec<Column<Advice>>,
+        rc: Vec<Column<Fixed>>,
+    ) -> PoseidonConfig<F> {
+        let t = params.t;
+        assert_eq!(state.len(), t);
+        assert_eq!(rc.len(), t);
+        for column in &state {
+            meta.enable_equality(*column);
+        }
+        meta.enable_constant(rc[0]);
+        let s_full = meta.selector();
+        let s_partial = meta.selector();
+
+        for (name, selector, full) in [
+            ("full round", s_full, true),
+            ("partial round", s_partial, false),
+        ] {
+            let m = params.m.clone();
+            let (state, rc) = (state.clone(), rc.clone());
+            meta.create_gate(name, move |meta| {
+                let s = meta.query_selector(selector);
+                let x: Vec<Expression<F>> = (0..t)
+                    .map(|j| {
+                        let x = meta.query_advice(state[j], Rotation::cur())
+                            + meta.query_fixed(rc[j], Rotation::cur());
+                        if full || j == 0 {
+                            let x2 = x.clone() * x.clone();
+                            x2.clone() * x2 * x
+                        } else {
+                            x
+                        }
+                    })
+                    .collect();
+                (0..t)
+                    .map(|i| {
+                        let next = meta.query_advice(state[i], Rotation::next());
+                        let mix = x
+                            .iter()
+                            .zip(&m[i])
+                            .fold(Expression::Constant(F::zero()), |acc, (x, mij)| {
+                                acc + x.clone() * Expression::Constant(*mij)
+                            });
+                        s.clone() * (next - mix)
+                    })
+                    .collect::<Vec<_>>()
+            });
+        }
+
+        PoseidonConfig {
+            params,
+            state,
+            rc,
+            s_full,
+            s_partial,
+        }
+    }
+
+    // constrains the permutation of width state.len(), returning the state
+    // after the last round
+    pub fn permutation(
+        &self,
+        layouter: impl Layouter<F>,
+        state: &[AssignedCell<F, F>],
+    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
+        if state.len() != self.config.params.t {
+            return Err(Error::Synthesis);
+        }
+        self.rounds(layouter, None, state)
+    }
+
+    // constrains Poseidon::hash(inputs), with inputs.len() == width - 1
+    pub fn hash(
+        &self,
+        layouter: impl Layouter<F>,
+        inputs: &[AssignedCell<F, F>],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        if inputs.len() + 1 != self.config.params.t {
+            return Err(Error::Synthesis);
+        }
+        let mut state = self.rounds(layouter, Some(F::zero()), inputs)?;
+        Ok(state.swap_remove(0))
+    }
+
+    // the initial state is the constant capacity, if any, followed by the
+    // copies of the cells in state
+    fn rounds(
+        &self,
+        mut layouter: impl Layouter<F>,
+        capacity: Option<F>,
+        state: &[AssignedCell<F, F>],
+    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
+        let config = &self.config;
+        let params = &config.params;
+        layouter.assign_region(
+            || "poseidon",
+            |mut region| {
+                let mut cells = Vec::with_capacity(params.t);
+                if let Some(c) = capacity {
+                    cells.push(region.assign_advice_from_constant(
+                        || "capacity",
+                        config.state[0],
+                        0,
+                        c,
+                    )?);
+                }
+                for cell in state {
+                    let column = config.state[cells.len()];
+                    cells.push(cell.copy_advice(|| "input", &mut region, column, 0)?);
+                }
+
+                let mut values: Option<Vec<F>> = cells.iter().map(|c| c.value().copied()).collect();
+                for i in 0..params.n_rounds() {
+                    if params.is_full_round(i) {
+                        config.s_full.enable(&mut region, i)?;
+                    } else {
+                        config.s_partial.enable(&mut region, i)?;
+                    }
+                    for (j, column) in config.rc.iter().enumerate() {
+                        let c = params.c[i * params.t + j];
+                        region.assign_fixed(|| "round constant", *column, i, || Ok(c))?;
+                    }
+
+                    values = values.map(|v| params.round(&v, i));
+                    cells = config
+                        .state
+                        .iter()
+                        .enumerate()
+                        .map(|(j, column)| {
+                            region.assign_advice(
+                                || "state",
+                                *column,
+                                i + 1,
+                                || values.as_ref().map(|v| v[j]).ok_or(Error::Synthesis),
+                            )
+                        })
+                        .collect::<Result<_, _>>()?;
+                }
+                Ok(cells)
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Poseidon;
+    use halo2_proofs::arithmetic::Field as _;
+    use halo2_proofs::circuit::SimpleFloorPlanner;
+    use halo2_proofs::dev::MockProver;
+    use halo2_proofs::plonk::{Circuit, Instance};
+
+    #[derive(Clone, Debug)]
+    struct HashConfig {
+        poseidon: PoseidonConfig<Bn254Fr>,
+        input: Column<Advice>,
+        output: Column<Instance>,
+    }
+
+    // proves that the public output is the hash of N private inputs
+    #[derive(Default)]
+    struct HashCircuit<const N: usize> {
+        inputs: Option<[Bn254Fr; N]>,
+    }
+
+    impl<const N: usize> Circuit<Bn254Fr> for HashCircuit<N> {
+        type Config = HashConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            HashCircuit { inputs: None }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Bn254Fr>) -> HashConfig {
+            let params = PoseidonParams::new(N + 1).unwrap();
+            let state = (0..=N).map(|_| meta.advice_column()).collect();
+            let rc = (0..=N).map(|_| meta.fixed_column()).collect();
+            let input = meta.advice_column();
+            let output = meta.instance_column();
+            meta.enable_equality(input);
+            meta.enable_equality(output);
+            HashConfig {
+                poseidon: PoseidonChip::configure(meta, params, state, rc),
+                input,
+                output,
+            }
+        }
+
+        fn synthesize(
+            &self,
+            config: HashConfig,
+            mut layouter: impl Layouter<Bn254Fr>,
+        ) -> Result<(), Error> {
+            let inputs = layouter.assign_region(
+                || "inputs",
+                |mut region| {
+                    (0..N)
+                        .map(|i| {
+                            region.assign_advice(
+                                || "input",
+                                config.input,
+                                i,
+                                || self.inputs.map(|v| v[i]).ok_or(Error::Synthesis),
+                            )
+                        })
+                        .collect::<Result<Vec<_>, _>>()
+                },
+            )?;
+            let chip = PoseidonChip::construct(config.poseidon);
+            let out = chip.hash(layouter.namespace(|| "hash"), &inputs)?;
+            layouter.constrain_instance(out.cell(), config.output, 0)
+        }
+    }
+
+    fn check<const N: usize>() {
+        let inp: Vec<Fr> = (1..=N as u64).map(Fr::from_u64).collect();
+        let expected = to_field::<Bn254Fr>(&Poseidon::new().hash(inp.clone()).unwrap()).unwrap();
+        let mut inputs = [Bn254Fr::zero(); N];
+        for (x, e) in inputs.iter_mut().zip(&inp) {
+            *x = to_field(e).unwrap();
+        }
+        let circuit = HashCircuit::<N> {
+            inputs: Some(inputs),
+        };
+
+        let prover = MockProver::run(7, &circuit, vec![vec![expected]]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+
+        let prover = MockProver::run(7, &circuit, vec![vec![expected + Bn254Fr::one()]]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_chip() {
+        check::<1>();
+        check::<2>();
+        check::<3>();
+        check::<4>();
+        check::<5>();
+        check::<6>();
+        check::<7>();
+        check::<8>();
+        check::<9>();
+        check::<10>();
+        check::<11>();
+        check::<12>();
+        check::<13>();
+        check::<14>();
+        check::<15>();
+        check::<16>();
+    }
+
+    #[test]
+    fn test_params() {
+        let params = PoseidonParams::<Bn254Fr>::new(3).unwrap();
+        let poseidon = Poseidon::new();
+        let mut state = [Fr::zero(), Fr::from_u64(1), Fr::from_u64(2)];
+        let mut s: Vec<Bn254Fr> = state.iter().map(|e| to_field(e).unwrap()).collect();
+        for i in 0..params.n_rounds() {
+            s = params.round(&s, i);
+        }
+        poseidon.permutation(&mut state).unwrap();
+        let expected: Vec<Bn254Fr> = state.iter().map(|e| to_field(e).unwrap()).collect();
+        assert_eq!(s, expected);
+
+        assert_eq!(
+            PoseidonParams::<Bn254Fr>::new(18).err(),
+            Some(PoseidonError::UnsupportedWidth(18))
+        );
+        assert!(PoseidonParams::<halo2_proofs::pasta::Fp>::new(3).is_err());
+    }
+}
@@ -0,0 +1,415 @@
+// BN254's scalar field for halo2_proofs, which only ships the pasta fields.
+// Bn254Fr wraps Fr, keeping its Montgomery form (R = 2^256), and implements
+// the ff 0.12 and pasta_curves traits required by FieldExt on top of the
+// arithmetic of Fr. The constants below are in Montgomery form too, and are
+// checked against their definitions in the tests.
+use core::cmp::Ordering;
+use core::convert::TryFrom;
+use core::fmt;
+use core::ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+use halo2_proofs::arithmetic::{FieldExt, Group, SqrtRatio};
+use halo2_proofs::pasta::group::ff::{self, Field as _, PrimeField as _};
+use rand_core::RngCore;
+use subtle::{Choice, ConditionallySelectable, ConstantTimeEq, CtOption};
+
+use crate::fr::{Field as _, PrimeField as _};
+use crate::{Fr, FrRepr};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Bn254Fr(pub Fr);
+
+const fn mont(limbs: [u64; 4]) -> Bn254Fr {
+    Bn254Fr(Fr(FrRepr(limbs)))
+}
+
+// 2^28-th root of unity, 7^((r - 1) / 2^28)
+const ROOT_OF_UNITY: Bn254Fr = mont([
+    0x9632c7c5b639feb8,
+    0x985ce3400d0ff299,
+    0xb2dd880001b0ecd8,
+    0x1d69070d6d98ce29,
+]);
+const ROOT_OF_UNITY_INV: Bn254Fr = mont([
+    0x05f05c05affb3d96,
+    0xb8e594ebfc3b5137,
+    0x60314620b85bc4c1,
+    0x2a4129bebb6fc591,
+]);
+// 7^(2^28)
+const DELTA: Bn254Fr = mont([
+    0x9a0c322befd78855,
+    0x46e82d14249b563c,
+    0x5983a663e0b0b7a7,
+    0x22ab452baaa111ad,
+]);
+const TWO_INV: Bn254Fr = mont([
+    0x783c14d81ffffffe,
+    0xaf982f6f0c8d1edd,
+    0x8f5f7492fcfd4f45,
+    0x1f37631a3d9cbfac,
+]);
+// cube root of unity, 7^((r - 1) / 3)
+const ZETA: Bn254Fr = mont([
+    0x93e7cede4a0329b3,
+    0x7d4fdca77a96c167,
+    0x8be4ba08b19a750a,
+    0x1cbd5653a5661c25,
+]);
+const TWO_64: Bn254Fr = mont([
+    0xb4c6edf97c5fb586,
+    0x708c8d50bfeb93be,
+    0x9ffd1de404f7e0ef,
+    0x215b02ac9a392866,
+]);
+
+impl Bn254Fr {
+    fn limbs(&self) -> [u64; 4] {
+        self.0.into_raw_repr().0
+    }
+}
+
+impl From<Fr> for Bn254Fr {
+    fn from(e: Fr) -> Bn254Fr {
+        Bn254Fr(e)
+    }
+}
+
+impl From<Bn254Fr> for Fr {
+    fn from(e: Bn254Fr) -> Fr {
+        e.0
+    }
+}
+
+impl From<u64> for Bn254Fr {
+    fn from(n: u64) -> Bn254Fr {
+        Bn254Fr(Fr::from_u64(n))
+    }
+}
+
+impl From<bool> for Bn254Fr {
+    fn from(b: bool) -> Bn254Fr {
+        Bn254Fr::from(b as u64)
+    }
+}
+
+impl Default for Bn254Fr {
+    fn default() -> Bn254Fr {
+        Bn254Fr::zero()
+    }
+}
+
+impl fmt::Debug for Bn254Fr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Ord for Bn254Fr {
+    fn cmp(&self, other: &Bn254Fr) -> Ordering {
+        self.0.into_repr().cmp(&other.0.into_repr())
+    }
+}
+
+impl PartialOrd for Bn254Fr {
+    fn partial_cmp(&self, other: &Bn254Fr) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl ConstantTimeEq for Bn254Fr {
+    fn ct_eq(&self, other: &Bn254Fr) -> Choice {
+        self.limbs()[..].ct_eq(&other.limbs()[..])
+    }
+}
+
+impl ConditionallySelectable for Bn254Fr {
+    fn conditional_select(a: &Bn254Fr, b: &Bn254Fr, choice: Choice) -> Bn254Fr {
+        let mut r = a.limbs();
+        for (x, y) in r.iter_mut().zip(b.limbs()) {
+            *x = u64::conditional_select(x, &y, choice);
+        }
+        mont(r)
+    }
+}
+
+// the by value and by reference operators of ff, over the in-place
+// arithmetic of Fr
+macro_rules! impl_op {
+    ($op:ident, $op_fn:ident, $assign:ident, $assign_fn:ident) => {
+        impl<'a> $assign<&'a Bn254Fr> for Bn254Fr {
+            fn $assign_fn(&mut self, rhs: &'a Bn254Fr) {
+                self.0.$assign_fn(&rhs.0);
+            }
+        }
+
+        impl $assign for Bn254Fr {
+            fn $assign_fn(&mut self, rhs: Bn254Fr) {
+                self.0.$assign_fn(&rhs.0);
+            }
+        }
+
+        impl<'a> $op<&'a Bn254Fr> for Bn254Fr {
+            type Output = Bn254Fr;
+
+            fn $op_fn(mut self, rhs: &'a Bn254Fr) -> Bn254Fr {
+                self.0.$assign_fn(&rhs.0);
+                self
+            }
+        }
+
+        impl $op for Bn254Fr {
+            type Output = Bn254Fr;
+
+            fn $op_fn(mut self, rhs: Bn254Fr) -> Bn254Fr {
+                self.0.$assign_fn(&rhs.0);
+                self
+            }
+        }
+    };
+}
+
+impl_op!(Add, add, AddAssign, add_assign);
+impl_op!(Sub, sub, SubAssign, sub_assign);
+impl_op!(Mul, mul, MulAssign, mul_assign);
+
+impl Neg for Bn254Fr {
+    type Output = Bn254Fr;
+
+    fn neg(mut self) -> Bn254Fr {
+        self.0.negate();
+        self
+    }
+}
+
+impl ff::Field for Bn254Fr {
+    fn random(mut rng: impl RngCore) -> Bn254Fr {
+        let mut bytes = [0u8; 64];
+        rng.fill_bytes(&mut bytes);
+        Bn254Fr::from_bytes_wide(&bytes)
+    }
+
+    fn zero() -> Bn254Fr {
+        Bn254Fr(Fr::zero())
+    }
+
+    fn one() -> Bn254Fr {
+        Bn254Fr(Fr::one())
+    }
+
+    fn square(&self) -> Bn254Fr {
+        let mut r = *self;
+        r.0.square();
+        r
+    }
+
+    fn double(&self) -> Bn254Fr {
+        let mut r = *self;
+        r.0.double();
+        r
+    }
+
+    fn invert(&self) -> CtOption<Bn254Fr> {
+        match self.0.inverse() {
+            Some(inv) => CtOption::new(Bn254Fr(inv), Choice::from(1)),
+            None => CtOption::new(Bn254Fr::zero(), Choice::from(0)),
+        }
+    }
+
+    // Tonelli-Shanks, in variable time
+    fn sqrt(&self) -> CtOption<Bn254Fr> {
+        if self.0.is_zero() {
+            return CtOption::new(*self, Choice::from(1));
+        }
+        // with a = self: x = a^((t + 1) / 2) and b = a^t, so that x^2 = a * b
+        let w = self.pow_vartime(Bn254Fr::T_MINUS1_OVER2);
+        let mut x = w * self;
+        let mut b = x * w;
+        let mut z = ROOT_OF_UNITY;
+        let mut v = Bn254Fr::S;
+        while b != Bn254Fr::one() {
+            // the order of b is 2^k, it is 2^S only if a is not a square
+            let mut k = 0;
+            let mut b2k = b;
+            while b2k != Bn254Fr::one() {
+                b2k = b2k.square();
+                k += 1;
+                if k == v {
+                    return CtOption::new(Bn254Fr::zero(), Choice::from(0));
+                }
+            }
+            let mut w = z;
+            for _ in 0..v - k - 1 {
+                w = w.square();
+            }
+            z = w.square();
+            b *= z;
+            x *= w;
+            v = k;
+        }
+        CtOption::new(x, Choice::from(1))
+    }
+}
+
+impl ff::PrimeField for Bn254Fr {
+    // little-endian
+    type Repr = [u8; 32];
+
+    fn from_repr(repr: [u8; 32]) -> CtOption<Bn254Fr> {
+        match Fr::from_bytes_le(&repr) {
+            Ok(e) => CtOption::new(Bn254Fr(e), Choice::from(1)),
+            Err(_) => CtOption::new(Bn254Fr::zero(), Choice::from(0)),
+        }
+    }
+
+    fn to_repr(&self) -> [u8; 32] {
+        self.0.to_bytes_le()
+    }
+
+    fn is_odd(&self) -> Choice {
+        Choice::from(self.0.into_repr().0[0] as u8 & 1)
+    }
+
+    const NUM_BITS: u32 = 254;
+    const CAPACITY: u32 = 253;
+    const S: u32 = 28;
+
+    fn multiplicative_generator() -> Bn254Fr {
+        Bn254Fr(Fr::multiplicative_generator())
+    }
+
+    fn root_of_unity() -> Bn254Fr {
+        ROOT_OF_UNITY
+    }
+}
+
+impl SqrtRatio for Bn254Fr {
+    // (t - 1) / 2, where r - 1 = 2^S * t
+    const T_MINUS1_OVER2: [u64; 4] = [
+        0xcdcb848a1f0fac9f,
+        0x0c0ac2e9419f4243,
+        0x098d014dc2822db4,
+        0x0000000183227397,
+    ];
+
+    fn get_lower_32(&self) -> u32 {
+        self.0.into_repr().0[0] as u32
+    }
+}
+
+impl Group for Bn254Fr {
+    type Scalar = Bn254Fr;
+
+    fn group_zero() -> Bn254Fr {
+        Bn254Fr::zero()
+    }
+
+    fn group_add(&mut self, rhs: &Bn254Fr) {
+        *self += rhs;
+    }
+
+    fn group_sub(&mut self, rhs: &Bn254Fr) {
+        *self -= rhs;
+    }
+
+    fn group_scale(&mut self, by: &Bn254Fr) {
+        *self *= by;
+    }
+}
+
+impl FieldExt for Bn254Fr {
+    const MODULUS: &'static str =
+        "0x30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000001";
+    const ROOT_OF_UNITY_INV: Bn254Fr = ROOT_OF_UNITY_INV;
+    const DELTA: Bn254Fr = DELTA;
+    const TWO_INV: Bn254Fr = TWO_INV;
+    const ZETA: Bn254Fr = ZETA;
+
+    fn from_u128(n: u128) -> Bn254Fr {
+        Bn254Fr(Fr::from_u128(n))
+    }
+
+    // little-endian, reduced modulo r
+    fn from_bytes_wide(bytes: &[u8; 64]) -> Bn254Fr {
+        bytes
+            .chunks_exact(8)
+            .rev()
+            .fold(Bn254Fr::zero(), |acc, chunk| {
+                acc * TWO_64
+                    + Bn254Fr::from(u64::from_le_bytes(<[u8; 8]>::try_from(chunk).unwrap()))
+            })
+    }
+
+    fn get_lower_128(&self) -> u128 {
+        let limbs = self.0.into_repr().0;
+        limbs[0] as u128 | ((limbs[1] as u128) << 64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pow2(e: Bn254Fr, n: u32) -> Bn254Fr {
+        (0..n).fold(e, |acc, _| acc.square())
+    }
+
+    #[test]
+    fn test_constants() {
+        let g = Bn254Fr::multiplicative_generator();
+        assert_eq!(g, Bn254Fr::from(7));
+        assert_eq!(Bn254Fr::from_str_vartime(Bn254Fr::MODULUS), None);
+
+        let root = Bn254Fr::root_of_unity();
+        assert_eq!(pow2(root, Bn254Fr::S), Bn254Fr::one());
+        assert_ne!(pow2(root, Bn254Fr::S - 1), Bn254Fr::one());
+        assert_eq!(root * Bn254Fr::ROOT_OF_UNITY_INV, Bn254Fr::one());
+        assert_eq!(Bn254Fr::DELTA, pow2(g, Bn254Fr::S));
+        assert_eq!(Bn254Fr::TWO_INV.double(), Bn254Fr::one());
+        assert_ne!(Bn254Fr::ZETA, Bn254Fr::one());
+        assert_eq!(Bn254Fr::ZETA.square() * Bn254Fr::ZETA, Bn254Fr::one());
+        assert_eq!(TWO_64, Bn254Fr::from(u64::MAX) + Bn254Fr::one());
+
+        // g^t has order 2^S
+        let w = g.pow_vartime(Bn254Fr::T_MINUS1_OVER2);
+        assert_eq!(w.square() * g, root);
+    }
+
+    #[test]
+    fn test_ops() {
+        let a = Bn254Fr::from_u128(u128::MAX);
+        let b = Bn254Fr::from(12345);
+        assert_eq!(a.get_lower_128(), u128::MAX);
+        assert_eq!((a + b) - b, a);
+        assert_eq!(a * b * b.invert().unwrap(), a);
+        assert_eq!(-a + a, Bn254Fr::zero());
+        assert!(bool::from(Bn254Fr::zero().invert().is_none()));
+        assert!(b > Bn254Fr::one());
+        assert_eq!(Bn254Fr::conditional_select(&a, &b, Choice::from(1)), b);
+
+        assert_eq!(Bn254Fr::from_repr(a.to_repr()).unwrap(), a);
+        assert!(bool::from(Bn254Fr::from_repr([0xff; 32]).is_none()));
+        assert!(bool::from(b.is_odd()));
+
+        // 2^256 + 5
+        let mut wide = [0u8; 64];
+        wide[0] = 5;
+        wide[32] = 1;
+        let r = TWO_64.square().square() + Bn254Fr::from(5);
+        assert_eq!(Bn254Fr::from_bytes_wide(&wide), r);
+    }
+
+    #[test]
+    fn test_sqrt() {
+        assert_eq!(Bn254Fr::zero().sqrt().unwrap(), Bn254Fr::zero());
+        for n in 1..50u64 {
+            let a = Bn254Fr::from(n);
+            let s = a.square().sqrt().unwrap();
+            assert!(s == a || s == -a);
+        }
+        // 7 generates the multiplicative group, so it is not a square
+        assert!(bool::from(
+            Bn254Fr::multiplicative_generator().sqrt().is_none()
+        ));
+    }
+}
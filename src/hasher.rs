@@ -0,0 +1,167 @@
+// Poseidon hasher bound to a fixed number of inputs. The round constants and
+// the MDS matrix for its width are flattened into contiguous buffers when it
+// is created, and the state buffers are reused, so hashing with it never
+// allocates.
+use alloc::{vec, vec::Vec};
+
+use crate::fr::*;
+
+use crate::field::FieldBackend;
+use crate::permutation;
+use crate::{Constants, Fr, Poseidon, PoseidonError};
+
+pub struct PoseidonHasher {
+    t: usize,
+    n_rounds_f: usize,
+    n_rounds_p: usize,
+    // (n_rounds_f + n_rounds_p) * t round constants
+    c: Vec<Fr>,
+    // t * t matrix, row by row
+    m: Vec<Fr>,
+    state: Vec<Fr>,
+    scratch: Vec<Fr>,
+    // number of inputs absorbed since the last reset
+    absorbed: usize,
+    backend: FieldBackend,
+}
+
+impl PoseidonHasher {
+    pub fn new(poseidon: &Poseidon, n_inputs: usize) -> Result<PoseidonHasher, PoseidonError> {
+        Self::from_constants(&poseidon.constants, n_inputs)
+    }
+
+    pub fn from_constants(
+        constants: &Constants,
+        n_inputs: usize,
+    ) -> Result<PoseidonHasher, PoseidonError> {
+        if n_inputs == 0 || n_inputs > constants.n_rounds_p.len() {
+            return Err(PoseidonError::UnsupportedWidth(n_inputs + 1));
+        }
+        let t = n_inputs + 1;
+        let n_rounds_f = constants.n_rounds_f;
+        let n_rounds_p = constants.n_rounds_p[t - 2];
+        let n_constants = (n_rounds_f + n_rounds_p) * t;
+        if constants.c[t - 2].len() < n_constants
+            || constants.m[t - 2].len() != t
+            || constants.m[t - 2].iter().any(|row| row.len() != t)
+        {
+            return Err(PoseidonError::MalformedConstants(
+                "Wrong number of constants",
+            ));
+        }
+        Ok(PoseidonHasher {
+            t,
+            n_rounds_f,
+            n_rounds_p,
+            c: constants.c[t - 2][..n_constants].to_vec(),
+            m: constants.m[t - 2].concat(),
+            state: vec![Fr::zero(); t],
+            scratch: vec![Fr::zero(); t],
+            absorbed: 0,
+            backend: FieldBackend::detect(),
+        })
+    }
+
+    pub fn n_inputs(&self) -> usize {
+        self.t - 1
+    }
+
+    // clears the absorbed inputs, keeping the buffers
+    pub fn reset(&mut self) {
+        for s in self.state.iter_mut() {
+            *s = Fr::zero();
+        }
+        self.absorbed = 0;
+    }
+
+    pub fn update(&mut self, inp: &Fr) -> Result<(), PoseidonError> {
+        if self.absorbed == self.t - 1 {
+            return Err(PoseidonError::InputTooLong { max: self.t - 1 });
+        }
+        self.absorbed += 1;
+        self.state[self.absorbed] = *inp;
+        Ok(())
+    }
+
+    // hashes the absorbed inputs, which must be exactly n_inputs, and resets
+    // the hasher
+    pub fn finalize(&mut self) -> Result<Fr, PoseidonError> {
+        if self.absorbed != self.t - 1 {
+            return Err(PoseidonError::WrongInputsLength {
+                expected: self.t - 1,
+            });
+        }
+        self.permute();
+        let h = self.state[0];
+        self.reset();
+        Ok(h)
+    }
+
+    // same output as Poseidon::hash
+    pub fn hash(&mut self, inp: &[Fr]) -> Result<Fr, PoseidonError> {
+        if inp.len() != self.t - 1 {
+            return Err(PoseidonError::WrongInputsLength {
+                expected: self.t - 1,
+            });
+        }
+        self.state[0] = Fr::zero();
+        self.state[1..].copy_from_slice(inp);
+        self.absorbed = inp.len();
+        self.finalize()
+    }
+
+    fn permute(&mut self) {
+        permutation::permute_fr(
+            self.backend,
+            &mut self.state,
+            &mut self.scratch,
+            &self.c,
+            &self.m,
+            self.n_rounds_f,
+            self.n_rounds_p,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FrRepr;
+
+    #[test]
+    fn test_hasher() {
+        let poseidon = Poseidon::new();
+
+        let mut hasher = PoseidonHasher::new(&poseidon, 2).unwrap();
+        let h = hasher.hash(&[Fr::from_u64(1), Fr::from_u64(2)]).unwrap();
+        assert_eq!(
+            h.to_string(),
+            "Fr(0x115cc0f5e7d690413df64c6b9662e9cf2a3617f2743245519e19607a4417189a)"
+        );
+        // reusing the hasher gives the same result
+        assert_eq!(hasher.hash(&[Fr::from_u64(1), Fr::from_u64(2)]).unwrap(), h);
+
+        hasher.update(&Fr::from_u64(1)).unwrap();
+        hasher.update(&Fr::from_u64(2)).unwrap();
+        assert_eq!(
+            hasher.update(&Fr::from_u64(3)),
+            Err(PoseidonError::InputTooLong { max: 2 })
+        );
+        assert_eq!(hasher.finalize().unwrap(), h);
+
+        hasher.update(&Fr::from_u64(1)).unwrap();
+        hasher.finalize().expect_err("Wrong inputs length");
+        hasher.reset();
+        hasher
+            .hash(&[Fr::from_u64(1)])
+            .expect_err("Wrong inputs length");
+
+        for n in 1..17 {
+            let inp: Vec<Fr> = (0..n as u64).map(Fr::from_u64).collect();
+            let mut hasher = PoseidonHasher::new(&poseidon, n).unwrap();
+            assert_eq!(hasher.hash(&inp).unwrap(), poseidon.hash(inp).unwrap());
+        }
+        assert!(PoseidonHasher::new(&poseidon, 0).is_err());
+        assert!(PoseidonHasher::new(&poseidon, 17).is_err());
+    }
+}
@@ -0,0 +1,278 @@
+use alloc::collections::BTreeMap;
+use alloc::{vec, vec::Vec};
+
+use crate::fr::*;
+
+use crate::merkle_tree::{MerkleProof, MerkleTree};
+use crate::{Fr, FrRepr, Poseidon, PoseidonError};
+
+// Leaves form a linked list sorted by value, the leaf holding the biggest
+// value points to index 0 and next_value 0.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IndexedLeaf {
+    pub value: Fr,
+    pub next_index: usize,
+    pub next_value: Fr,
+}
+
+// Proof that the leaf at proof.index is the low leaf of a value: the value
+// falls strictly between leaf.value and leaf.next_value.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LowLeafProof {
+    pub leaf: IndexedLeaf,
+    pub proof: MerkleProof,
+}
+
+// Data needed to check an insertion: the low leaf before being updated with
+// its path in the tree before the update, and the path of the new leaf slot
+// once the low leaf has been updated.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InsertionWitness {
+    pub low_leaf: LowLeafProof,
+    pub new_leaf: IndexedLeaf,
+    pub new_leaf_proof: MerkleProof,
+}
+
+pub struct IndexedMerkleTree<'a> {
+    tree: MerkleTree<'a>,
+    leaves: Vec<IndexedLeaf>,
+    // value -> leaf index
+    sorted: BTreeMap<FrRepr, usize>,
+}
+
+impl IndexedLeaf {
+    pub fn hash(&self, poseidon: &Poseidon) -> Fr {
+        let next_index = Fr::from_repr(FrRepr::from(self.next_index as u64)).unwrap();
+        poseidon
+            .hash(vec![self.value, next_index, self.next_value])
+            .unwrap()
+    }
+
+    // checks that value falls in the range covered by the leaf
+    pub fn is_low_leaf_of(&self, value: &Fr) -> bool {
+        let v = value.into_repr();
+        self.value.into_repr() < v && (self.next_value.is_zero() || v < self.next_value.into_repr())
+    }
+}
+
+impl LowLeafProof {
+    pub fn verify(&self, poseidon: &Poseidon, root: &Fr, value: &Fr) -> bool {
+        self.leaf.is_low_leaf_of(value)
+            && self.proof.verify(poseidon, root, &self.leaf.hash(poseidon))
+    }
+}
+
+impl InsertionWitness {
+    // checks the insertion of value moving the tree from old_root to new_root
+    pub fn verify(&self, poseidon: &Poseidon, old_root: &Fr, new_root: &Fr, value: &Fr) -> bool {
+        if !self.low_leaf.verify(poseidon, old_root, value)
+            || self.new_leaf.value != *value
+            || self.new_leaf.next_index != self.low_leaf.leaf.next_index
+            || self.new_leaf.next_value != self.low_leaf.leaf.next_value
+        {
+            return false;
+        }
+        let updated_low_leaf = IndexedLeaf {
+            value: self.low_leaf.leaf.value,
+            next_index: self.new_leaf_proof.index,
+            next_value: *value,
+        };
+        let intermediate_root = self
+            .low_leaf
+            .proof
+            .compute_root(poseidon, &updated_low_leaf.hash(poseidon));
+        self.new_leaf_proof
+            .verify(poseidon, &intermediate_root, &Fr::zero())
+            && self
+                .new_leaf_proof
+                .verify(poseidon, new_root, &self.new_leaf.hash(poseidon))
+    }
+}
+
+impl<'a> IndexedMerkleTree<'a> {
+    pub fn new(
+        poseidon: &'a Poseidon,
+        depth: usize,
+    ) -> Result<IndexedMerkleTree<'a>, PoseidonError> {
+        let mut tree = MerkleTree::new(poseidon, depth)?;
+        let first = IndexedLeaf {
+            value: Fr::zero(),
+            next_index: 0,
+            next_value: Fr::zero(),
+        };
+        tree.push(first.hash(tree.poseidon()))?;
+        let mut sorted = BTreeMap::new();
+        sorted.insert(first.value.into_repr(), 0);
+        Ok(IndexedMerkleTree {
+            tree,
+            leaves: vec![first],
+            sorted,
+        })
+    }
+
+    pub fn root(&self) -> Fr {
+        self.tree.root()
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    pub fn leaf(&self, index: usize) -> Option<&IndexedLeaf> {
+        self.leaves.get(index)
+    }
+
+    pub fn contains(&self, value: &Fr) -> bool {
+        self.sorted.contains_key(&value.into_repr())
+    }
+
+    pub fn poseidon(&self) -> &'a Poseidon {
+        self.tree.poseidon()
+    }
+
+    fn low_leaf_index(&self, value: &Fr) -> Result<usize, PoseidonError> {
+        let v = value.into_repr();
+        if self.sorted.contains_key(&v) {
+            return Err(PoseidonError::DuplicateValue);
+        }
+        // the zero leaf is always present, so any non-zero value has a low leaf
+        let (_, index) = self
+            .sorted
+            .range(..v)
+            .next_back()
+            .expect("Missing zero leaf");
+        Ok(*index)
+    }
+
+    pub fn low_leaf_proof(&self, value: &Fr) -> Result<LowLeafProof, PoseidonError> {
+        let index = self.low_leaf_index(value)?;
+        Ok(LowLeafProof {
+            leaf: self.leaves[index],
+            proof: self.tree.proof(index)?,
+        })
+    }
+
+    pub fn proof(&self, value: &Fr) -> Result<(IndexedLeaf, MerkleProof), PoseidonError> {
+        match self.sorted.get(&value.into_repr()) {
+            Some(index) => Ok((self.leaves[*index], self.tree.proof(*index)?)),
+            None => Err(PoseidonError::ValueNotFound),
+        }
+    }
+
+    pub fn insert(&mut self, value: Fr) -> Result<InsertionWitness, PoseidonError> {
+        let index = self.leaves.len();
+        if index >= self.tree.capacity() {
+            return Err(PoseidonError::TreeFull);
+        }
+        let low_leaf = self.low_leaf_proof(&value)?;
+        let low_index = low_leaf.proof.index;
+
+        let new_leaf = IndexedLeaf {
+            value,
+            next_index: low_leaf.leaf.next_index,
+            next_value: low_leaf.leaf.next_value,
+        };
+        self.leaves[low_index].next_index = index;
+        self.leaves[low_index].next_value = value;
+        let low_hash = self.leaves[low_index].hash(self.tree.poseidon());
+        self.tree.set(low_index, low_hash)?;
+        let new_leaf_proof = self.tree.proof(index)?;
+
+        let new_hash = new_leaf.hash(self.tree.poseidon());
+        self.tree.set(index, new_hash)?;
+        self.leaves.push(new_leaf);
+        self.sorted.insert(value.into_repr(), index);
+
+        Ok(InsertionWitness {
+            low_leaf,
+            new_leaf,
+            new_leaf_proof,
+        })
+    }
+
+    // inserts the values in order, returning one witness per value; values
+    // are checked before inserting so a failing batch leaves the tree untouched
+    pub fn batch_insert(&mut self, values: &[Fr]) -> Result<Vec<InsertionWitness>, PoseidonError> {
+        if self.leaves.len() + values.len() > self.tree.capacity() {
+            return Err(PoseidonError::TreeFull);
+        }
+        let mut seen = BTreeMap::new();
+        for value in values {
+            let v = value.into_repr();
+            if value.is_zero() || self.sorted.contains_key(&v) || seen.insert(v, ()).is_some() {
+                return Err(PoseidonError::DuplicateValue);
+            }
+        }
+        values.iter().map(|value| self.insert(*value)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert() {
+        let poseidon = Poseidon::new();
+        let mut tree = IndexedMerkleTree::new(&poseidon, 4).unwrap();
+
+        for v in [30, 10, 20].iter() {
+            let old_root = tree.root();
+            let witness = tree.insert(Fr::from_u64(*v)).unwrap();
+            assert!(witness.verify(&poseidon, &old_root, &tree.root(), &Fr::from_u64(*v)));
+            assert!(!witness.verify(&poseidon, &old_root, &tree.root(), &Fr::from_u64(*v + 1)));
+        }
+        assert_eq!(
+            tree.insert(Fr::from_u64(20)).err(),
+            Some(PoseidonError::DuplicateValue)
+        );
+        tree.insert(Fr::zero())
+            .expect_err("Value already in the tree");
+
+        // leaves are linked in increasing order: 0 -> 10 -> 20 -> 30 -> 0
+        assert_eq!(tree.leaf(0).unwrap().next_value, Fr::from_u64(10));
+        assert_eq!(tree.leaf(2).unwrap().next_value, Fr::from_u64(20));
+        assert_eq!(tree.leaf(3).unwrap().next_value, Fr::from_u64(30));
+        assert_eq!(tree.leaf(1).unwrap().next_index, 0);
+        assert!(tree.leaf(1).unwrap().next_value.is_zero());
+
+        let mut batched = IndexedMerkleTree::new(&poseidon, 4).unwrap();
+        batched
+            .batch_insert(&[Fr::from_u64(30), Fr::from_u64(10), Fr::from_u64(20)])
+            .unwrap();
+        assert_eq!(batched.root(), tree.root());
+        batched
+            .batch_insert(&[Fr::from_u64(40), Fr::from_u64(40)])
+            .expect_err("Value already in the tree");
+        assert_eq!(batched.root(), tree.root());
+    }
+
+    #[test]
+    fn test_non_membership() {
+        let poseidon = Poseidon::new();
+        let mut tree = IndexedMerkleTree::new(&poseidon, 8).unwrap();
+        tree.batch_insert(&[Fr::from_u64(5), Fr::from_u64(100), Fr::from_u64(50)])
+            .unwrap();
+        let root = tree.root();
+
+        let proof = tree.low_leaf_proof(&Fr::from_u64(70)).unwrap();
+        assert_eq!(proof.leaf.value, Fr::from_u64(50));
+        assert!(proof.verify(&poseidon, &root, &Fr::from_u64(70)));
+        assert!(!proof.verify(&poseidon, &root, &Fr::from_u64(100)));
+        assert!(!proof.verify(&poseidon, &root, &Fr::from_u64(40)));
+
+        // values above the max are covered by the last leaf
+        let proof = tree.low_leaf_proof(&Fr::from_u64(1000)).unwrap();
+        assert_eq!(proof.leaf.value, Fr::from_u64(100));
+        assert!(proof.verify(&poseidon, &root, &Fr::from_u64(1000)));
+
+        tree.low_leaf_proof(&Fr::from_u64(50))
+            .expect_err("Value already in the tree");
+        let (leaf, proof) = tree.proof(&Fr::from_u64(50)).unwrap();
+        assert!(proof.verify(&poseidon, &root, &leaf.hash(&poseidon)));
+    }
+}
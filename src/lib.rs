@@ -1,15 +1,82 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "std")]
 extern crate rand;
+#[cfg(feature = "std")]
 #[macro_use]
 extern crate ff;
-use ff::*;
+#[cfg(feature = "alloc")]
+use alloc::{vec, vec::Vec};
+#[cfg(feature = "alloc")]
+use fr::*;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 
+#[cfg(feature = "std")]
 #[derive(PrimeField)]
 #[PrimeFieldModulus = "21888242871839275222246405745257275088548364400416034343698204186575808495617"]
 #[PrimeFieldGenerator = "7"]
 pub struct Fr(FrRepr);
 
+// without std, the field arithmetic is implemented in fr.rs
+#[cfg(not(feature = "std"))]
+#[derive(Copy, Clone, PartialEq, Eq, Default)]
+pub struct FrRepr(pub [u64; 4]);
+
+#[cfg(not(feature = "std"))]
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct Fr(FrRepr);
+
+#[cfg(feature = "codegen")]
+pub mod codegen;
+#[cfg(feature = "alloc")]
 mod constants;
+pub mod convert;
+mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod field;
+pub mod fixed;
+pub mod fr;
+#[cfg(feature = "bellman")]
+pub mod gadget;
+#[cfg(feature = "halo2")]
+pub mod halo2;
+#[cfg(feature = "alloc")]
+pub mod hasher;
+#[cfg(feature = "alloc")]
+pub mod indexed_merkle_tree;
+#[cfg(feature = "alloc")]
+pub mod merkle_tree;
+#[cfg(feature = "alloc")]
+pub mod mmr;
+#[cfg(feature = "alloc")]
+pub mod multiproof;
+mod permutation;
+#[cfg(feature = "alloc")]
+pub mod r1cs;
+#[cfg(feature = "serde")]
+pub mod serialize;
+#[cfg(feature = "std")]
+pub mod simd;
+#[cfg(feature = "alloc")]
+pub mod sponge;
+#[cfg(feature = "alloc")]
+pub mod trace;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
+pub use error::PoseidonError;
+pub use field::FieldBackend;
+
+pub(crate) const N_ROUNDS_F: usize = 8;
+pub(crate) const N_ROUNDS_P: [usize; 16] = [
+    56, 57, 56, 60, 60, 63, 64, 63, 60, 66, 60, 65, 70, 60, 64, 68,
+];
+
+#[cfg(feature = "alloc")]
 #[derive(Debug)]
 pub struct Constants {
     pub c: Vec<Vec<Fr>>,
@@ -17,13 +84,15 @@ pub struct Constants {
     pub n_rounds_f: usize,
     pub n_rounds_p: Vec<usize>,
 }
-pub fn load_constants() -> Constants {
-    let (c_str, m_str) = constants::constants();
+#[cfg(feature = "alloc")]
+pub fn load_constants() -> Result<Constants, PoseidonError> {
+    let (c_str, m_str) = (constants::C_STR, constants::M_STR);
     let mut c: Vec<Vec<Fr>> = Vec::new();
     for i in 0..c_str.len() {
         let mut cci: Vec<Fr> = Vec::new();
         for j in 0..c_str[i].len() {
-            let b: Fr = Fr::from_str(c_str[i][j]).unwrap();
+            let b: Fr = Fr::parse_dec(c_str[i][j])
+                .map_err(|_| PoseidonError::MalformedConstants("Invalid round constant"))?;
             cci.push(b);
         }
         c.push(cci);
@@ -34,90 +103,170 @@ pub fn load_constants() -> Constants {
         for j in 0..m_str[i].len() {
             let mut mij: Vec<Fr> = Vec::new();
             for k in 0..m_str[i][j].len() {
-                let b: Fr = Fr::from_str(m_str[i][j][k]).unwrap();
+                let b: Fr = Fr::parse_dec(m_str[i][j][k])
+                    .map_err(|_| PoseidonError::MalformedConstants("Invalid matrix element"))?;
                 mij.push(b);
             }
             mi.push(mij);
         }
         m.push(mi);
     }
-    Constants {
+    Ok(Constants {
         c,
         m,
-        n_rounds_f: 8,
-        n_rounds_p: vec![
-            56, 57, 56, 60, 60, 63, 64, 63, 60, 66, 60, 65, 70, 60, 64, 68,
-        ],
-    }
+        n_rounds_f: N_ROUNDS_F,
+        n_rounds_p: N_ROUNDS_P.to_vec(),
+    })
 }
 
+#[cfg(feature = "alloc")]
 pub struct Poseidon {
     constants: Constants,
+    // constants.m of each width flattened row by row
+    m: Vec<Vec<Fr>>,
+    backend: FieldBackend,
 }
+#[cfg(feature = "alloc")]
 impl Poseidon {
     pub fn new() -> Poseidon {
+        let constants = load_constants().expect("Malformed built-in constants");
         Poseidon {
-            constants: load_constants(),
+            m: constants.m.iter().map(|m| m.concat()).collect(),
+            constants,
+            backend: FieldBackend::detect(),
         }
     }
-    pub fn ark(&self, state: &mut Vec<Fr>, c: &Vec<Fr>, it: usize) {
+    pub fn ark(&self, state: &mut [Fr], c: &[Fr], it: usize) {
         for i in 0..state.len() {
             state[i].add_assign(&c[it + i]);
         }
     }
 
-    pub fn sbox(&self, n_rounds_f: usize, n_rounds_p: usize, state: &mut Vec<Fr>, i: usize) {
-        if i < n_rounds_f / 2 || i >= n_rounds_f / 2 + n_rounds_p {
+    pub fn sbox(&self, n_rounds_f: usize, n_rounds_p: usize, state: &mut [Fr], i: usize) {
+        if permutation::is_full_round(i, n_rounds_f, n_rounds_p) {
             for j in 0..state.len() {
-                let aux = state[j];
-                state[j].square();
-                state[j].square();
-                state[j].mul_assign(&aux);
+                self.pow5(&mut state[j]);
             }
         } else {
-            let aux = state[0];
-            state[0].square();
-            state[0].square();
-            state[0].mul_assign(&aux);
+            self.pow5(&mut state[0]);
         }
     }
 
-    pub fn mix(&self, state: &Vec<Fr>, m: &Vec<Vec<Fr>>) -> Vec<Fr> {
-        let mut new_state: Vec<Fr> = Vec::new();
+    fn pow5(&self, x: &mut Fr) {
+        let aux = *x;
+        let x2 = *x;
+        field::mul_assign(self.backend, x, &x2);
+        let x2 = *x;
+        field::mul_assign(self.backend, x, &x2);
+        field::mul_assign(self.backend, x, &aux);
+    }
+
+    pub fn mix(&self, state: &[Fr], m: &[Vec<Fr>]) -> Vec<Fr> {
+        let mut new_state = vec![Fr::zero(); state.len()];
         for i in 0..state.len() {
-            new_state.push(Fr::zero());
             for j in 0..state.len() {
                 let mut mij = m[i][j];
-                mij.mul_assign(&state[j]);
+                field::mul_assign(self.backend, &mut mij, &state[j]);
                 new_state[i].add_assign(&mij);
             }
         }
-        new_state.clone()
+        new_state
     }
 
-    pub fn hash(&self, inp: Vec<Fr>) -> Result<Fr, String> {
-        let t = inp.len() + 1;
-        // if inp.len() == 0 || inp.len() >= self.constants.n_rounds_p.len() - 1 {
-        if inp.is_empty() || inp.len() > self.constants.n_rounds_p.len() {
-            return Err("Wrong inputs length".to_string());
+    // applies the permutation for width state.len(), scratch must have the
+    // same length and is used to avoid allocating on each round
+    fn permute(&self, state: &mut [Fr], scratch: &mut [Fr]) {
+        let t = state.len();
+        permutation::permute_fr(
+            self.backend,
+            state,
+            scratch,
+            &self.constants.c[t - 2],
+            &self.m[t - 2],
+            self.constants.n_rounds_f,
+            self.constants.n_rounds_p[t - 2],
+        );
+    }
+
+    fn check_inputs_len(&self, n: usize) -> Result<(), PoseidonError> {
+        if n == 0 {
+            return Err(PoseidonError::EmptyInput);
+        }
+        let max = self.constants.n_rounds_p.len();
+        if n > max {
+            return Err(PoseidonError::InputTooLong { max });
         }
-        let n_rounds_f = self.constants.n_rounds_f.clone();
-        let n_rounds_p = self.constants.n_rounds_p[t - 2].clone();
+        Ok(())
+    }
+
+    // applies the permutation of width state.len() to state
+    pub fn permutation(&self, state: &mut [Fr]) -> Result<(), PoseidonError> {
+        if state.len() < 2 || state.len() > self.constants.n_rounds_p.len() + 1 {
+            return Err(PoseidonError::UnsupportedWidth(state.len()));
+        }
+        let mut scratch = vec![Fr::zero(); state.len()];
+        self.permute(state, &mut scratch);
+        Ok(())
+    }
+
+    pub fn hash(&self, inp: Vec<Fr>) -> Result<Fr, PoseidonError> {
+        self.hash_with_state(inp, Fr::zero())
+    }
+
+    // init_state is placed in the capacity element (state[0]), which allows
+    // domain separation without changing the number of inputs
+    pub fn hash_with_state(&self, inp: Vec<Fr>, init_state: Fr) -> Result<Fr, PoseidonError> {
+        self.check_inputs_len(inp.len())?;
+        let t = inp.len() + 1;
 
-        let mut state = vec![Fr::zero(); t];
+        let mut state = vec![init_state; t];
         state[1..].clone_from_slice(&inp);
+        let mut scratch = vec![Fr::zero(); t];
+        self.permute(&mut state, &mut scratch);
+
+        Ok(state[0])
+    }
 
-        for i in 0..(n_rounds_f + n_rounds_p) {
-            self.ark(&mut state, &self.constants.c[t - 2], i * t);
-            self.sbox(n_rounds_f, n_rounds_p, &mut state, i);
-            state = self.mix(&state, &self.constants.m[t - 2]);
+    // Hashes each input tuple, giving the same output as calling hash on
+    // each of them. The state buffers are allocated once per batch (or once
+    // per thread with the parallel feature) instead of once per hash.
+    pub fn hash_many<const N: usize>(&self, inputs: &[[Fr; N]]) -> Result<Vec<Fr>, PoseidonError> {
+        self.check_inputs_len(N)?;
+        Ok(self.hash_batch(inputs))
+    }
+
+    // same as hash_many, but each input can have a different length
+    pub fn hash_many_slices(&self, inputs: &[&[Fr]]) -> Result<Vec<Fr>, PoseidonError> {
+        for inp in inputs {
+            self.check_inputs_len(inp.len())?;
         }
+        Ok(self.hash_batch(inputs))
+    }
 
-        Ok(state[0])
+    fn hash_batch<I: AsRef<[Fr]> + Sync>(&self, inputs: &[I]) -> Vec<Fr> {
+        let max_t = self.constants.n_rounds_p.len() + 1;
+        let buffers = || (vec![Fr::zero(); max_t], vec![Fr::zero(); max_t]);
+        let hash = |(state, scratch): &mut (Vec<Fr>, Vec<Fr>), inp: &I| {
+            let inp = inp.as_ref();
+            let t = inp.len() + 1;
+            state[0] = Fr::zero();
+            state[1..t].copy_from_slice(inp);
+            self.permute(&mut state[..t], &mut scratch[..t]);
+            state[0]
+        };
+
+        #[cfg(feature = "parallel")]
+        let hashes = inputs.par_iter().map_init(buffers, hash).collect();
+        #[cfg(not(feature = "parallel"))]
+        let hashes = {
+            let mut b = buffers();
+            inputs.iter().map(|inp| hash(&mut b, inp)).collect()
+        };
+        hashes
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "alloc"))]
 mod tests {
     use super::*;
 
@@ -267,6 +416,60 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_hash_with_state() {
+        let b1: Fr = Fr::from_str("1").unwrap();
+        let b2: Fr = Fr::from_str("2").unwrap();
+
+        let poseidon = Poseidon::new();
+
+        let h = poseidon.hash(vec![b1, b2]).unwrap();
+        let h0 = poseidon.hash_with_state(vec![b1, b2], Fr::zero()).unwrap();
+        assert_eq!(h, h0);
+        let h1 = poseidon.hash_with_state(vec![b1, b2], b1).unwrap();
+        assert_ne!(h, h1);
+
+        let mut state = [b1, b1, b2];
+        poseidon.permutation(&mut state).unwrap();
+        assert_eq!(state[0], h1);
+        assert_eq!(
+            poseidon.permutation(&mut [b1]),
+            Err(PoseidonError::UnsupportedWidth(1))
+        );
+    }
+
+    #[test]
+    fn test_hash_many() {
+        let poseidon = Poseidon::new();
+
+        let inputs: Vec<[Fr; 2]> = (0..10u64)
+            .map(|i| {
+                [
+                    Fr::from_repr(FrRepr::from(i)).unwrap(),
+                    Fr::from_repr(FrRepr::from(i * i)).unwrap(),
+                ]
+            })
+            .collect();
+        let hashes = poseidon.hash_many(&inputs).unwrap();
+        for (inp, h) in inputs.iter().zip(&hashes) {
+            assert_eq!(*h, poseidon.hash(inp.to_vec()).unwrap());
+        }
+
+        let b1: Fr = Fr::from_str("1").unwrap();
+        let ones = [b1; 16];
+        let slices: Vec<&[Fr]> = vec![&inputs[3], &inputs[5][..1], &ones];
+        let hashes = poseidon.hash_many_slices(&slices).unwrap();
+        for (inp, h) in slices.iter().zip(&hashes) {
+            assert_eq!(*h, poseidon.hash(inp.to_vec()).unwrap());
+        }
+
+        let empty: Vec<[Fr; 0]> = vec![[]];
+        assert_eq!(poseidon.hash_many(&empty), Err(PoseidonError::EmptyInput));
+        poseidon
+            .hash_many_slices(&[&[b1; 17]])
+            .expect_err("Wrong inputs length");
+    }
+
     #[test]
     fn test_wrong_inputs() {
         let b0: Fr = Fr::from_str("0").unwrap();
@@ -279,5 +482,15 @@ mod tests {
             b1, b2, b0, b0, b0, b0, b0, b0, b0, b0, b0, b0, b0, b0, b0, b0, b0,
         ];
         poseidon.hash(big_arr).expect_err("Wrong inputs length");
+
+        assert_eq!(
+            poseidon.hash(vec![b0; 17]),
+            Err(PoseidonError::InputTooLong { max: 16 })
+        );
+        assert_eq!(poseidon.hash(vec![]), Err(PoseidonError::EmptyInput));
+        assert_eq!(
+            PoseidonError::InputTooLong { max: 16 }.to_string(),
+            "Input too long, max 16 elements"
+        );
     }
 }
@@ -0,0 +1,319 @@
+// Sparse Merkle tree of fixed depth over Poseidon(left, right), with empty
+// leaves set to zero. It backs the indexed tree, the multiproofs and the
+// Python MerkleTree, and only stores the nodes that differ from the root of
+// an empty subtree, so the memory grows with the number of leaves set and
+// not with their indices.
+use alloc::collections::BTreeMap;
+use alloc::{vec, vec::Vec};
+
+use crate::fr::*;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use crate::{Fr, Poseidon, PoseidonError};
+
+pub struct MerkleTree<'a> {
+    poseidon: &'a Poseidon,
+    depth: usize,
+    // zeros[k] is the root of an empty subtree of height k
+    zeros: Vec<Fr>,
+    // levels[k] holds the set nodes of height k by index, levels[depth]
+    // holds the root
+    levels: Vec<BTreeMap<usize, Fr>>,
+    // one past the index of the last leaf that has been set
+    len: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MerkleProof {
+    pub index: usize,
+    pub siblings: Vec<Fr>,
+}
+
+pub fn hash_node(poseidon: &Poseidon, left: &Fr, right: &Fr) -> Fr {
+    poseidon.hash(vec![*left, *right]).unwrap()
+}
+
+// hashes the nodes of a level by pairs, the last one being paired with zero
+// if the level has an odd number of nodes
+fn hash_level(poseidon: &Poseidon, nodes: &[Fr], zero: &Fr) -> Vec<Fr> {
+    #[cfg(feature = "parallel")]
+    let pairs = nodes.par_chunks(2);
+    #[cfg(not(feature = "parallel"))]
+    let pairs = nodes.chunks(2);
+    pairs
+        .map(|pair| hash_node(poseidon, &pair[0], pair.get(1).unwrap_or(zero)))
+        .collect()
+}
+
+impl<'a> MerkleTree<'a> {
+    pub fn new(poseidon: &'a Poseidon, depth: usize) -> Result<MerkleTree<'a>, PoseidonError> {
+        if depth == 0 || depth >= usize::BITS as usize {
+            return Err(PoseidonError::InvalidDepth(depth));
+        }
+        let mut zeros = vec![Fr::zero()];
+        for k in 0..depth {
+            zeros.push(hash_node(poseidon, &zeros[k], &zeros[k]));
+        }
+        Ok(MerkleTree {
+            poseidon,
+            depth,
+            zeros,
+            levels: vec![BTreeMap::new(); depth + 1],
+            len: 0,
+        })
+    }
+
+    pub fn from_leaves(
+        poseidon: &'a Poseidon,
+        depth: usize,
+        leaves: &[Fr],
+    ) -> Result<MerkleTree<'a>, PoseidonError> {
+        let mut tree = MerkleTree::new(poseidon, depth)?;
+        if leaves.len() > tree.capacity() {
+            return Err(PoseidonError::TreeFull);
+        }
+        let mut level = leaves.to_vec();
+        for k in 0..=depth {
+            let next = if k < depth {
+                hash_level(tree.poseidon, &level, &tree.zeros[k])
+            } else {
+                Vec::new()
+            };
+            let zero = tree.zeros[k];
+            tree.levels[k] = level
+                .into_iter()
+                .enumerate()
+                .filter(|(_, n)| *n != zero)
+                .collect();
+            level = next;
+        }
+        tree.len = leaves.len();
+        Ok(tree)
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn capacity(&self) -> usize {
+        1 << self.depth
+    }
+
+    // number of leaves up to the last one that has been set
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn root(&self) -> Fr {
+        self.node(self.depth, 0)
+    }
+
+    pub fn leaf(&self, index: usize) -> Fr {
+        self.node(0, index)
+    }
+
+    pub fn node(&self, level: usize, index: usize) -> Fr {
+        match self.levels[level].get(&index) {
+            Some(n) => *n,
+            None => self.zeros[level],
+        }
+    }
+
+    pub fn poseidon(&self) -> &'a Poseidon {
+        self.poseidon
+    }
+
+    fn parent(&self, level: usize, index: usize) -> Fr {
+        let i = index & !1;
+        hash_node(
+            self.poseidon,
+            &self.node(level, i),
+            &self.node(level, i + 1),
+        )
+    }
+
+    fn set_node(&mut self, level: usize, index: usize, node: Fr) {
+        if node == self.zeros[level] {
+            self.levels[level].remove(&index);
+        } else {
+            self.levels[level].insert(index, node);
+        }
+        if level == 0 && index >= self.len {
+            self.len = index + 1;
+        }
+    }
+
+    pub fn set(&mut self, index: usize, leaf: Fr) -> Result<(), PoseidonError> {
+        if index >= self.capacity() {
+            return Err(PoseidonError::IndexOutOfRange(index));
+        }
+        self.set_node(0, index, leaf);
+        for k in 0..self.depth {
+            let parent = self.parent(k, index >> k);
+            self.set_node(k + 1, index >> (k + 1), parent);
+        }
+        Ok(())
+    }
+
+    // sets several leaves at once, hashing every updated node only once
+    pub fn set_many(&mut self, leaves: &[(usize, Fr)]) -> Result<(), PoseidonError> {
+        if let Some((index, _)) = leaves.iter().find(|(index, _)| *index >= self.capacity()) {
+            return Err(PoseidonError::IndexOutOfRange(*index));
+        }
+        let mut dirty: Vec<usize> = Vec::with_capacity(leaves.len());
+        for (index, leaf) in leaves {
+            self.set_node(0, *index, *leaf);
+            dirty.push(*index);
+        }
+        for k in 0..self.depth {
+            let mut parents: Vec<usize> = dirty.iter().map(|i| i >> 1).collect();
+            parents.sort_unstable();
+            parents.dedup();
+            #[cfg(feature = "parallel")]
+            let iter = parents.par_iter();
+            #[cfg(not(feature = "parallel"))]
+            let iter = parents.iter();
+            let nodes: Vec<Fr> = iter.map(|p| self.parent(k, 2 * p)).collect();
+            for (p, node) in parents.iter().zip(nodes) {
+                self.set_node(k + 1, *p, node);
+            }
+            dirty = parents;
+        }
+        Ok(())
+    }
+
+    pub fn push(&mut self, leaf: Fr) -> Result<usize, PoseidonError> {
+        let index = self.len();
+        if index >= self.capacity() {
+            return Err(PoseidonError::TreeFull);
+        }
+        self.set(index, leaf)?;
+        Ok(index)
+    }
+
+    pub fn proof(&self, index: usize) -> Result<MerkleProof, PoseidonError> {
+        if index >= self.capacity() {
+            return Err(PoseidonError::IndexOutOfRange(index));
+        }
+        let siblings = (0..self.depth)
+            .map(|k| self.node(k, (index >> k) ^ 1))
+            .collect();
+        Ok(MerkleProof { index, siblings })
+    }
+}
+
+impl MerkleProof {
+    pub fn compute_root(&self, poseidon: &Poseidon, leaf: &Fr) -> Fr {
+        let mut node = *leaf;
+        for (k, sibling) in self.siblings.iter().enumerate() {
+            node = if (self.index >> k) & 1 == 0 {
+                hash_node(poseidon, &node, sibling)
+            } else {
+                hash_node(poseidon, sibling, &node)
+            };
+        }
+        node
+    }
+
+    pub fn verify(&self, poseidon: &Poseidon, root: &Fr, leaf: &Fr) -> bool {
+        if self.siblings.len() >= usize::BITS as usize || self.index >> self.siblings.len() != 0 {
+            return false;
+        }
+        self.compute_root(poseidon, leaf) == *root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FrRepr;
+
+    fn leaves(n: u64) -> Vec<Fr> {
+        (0..n)
+            .map(|i| Fr::from_repr(FrRepr::from(i + 1)).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_root() {
+        let poseidon = Poseidon::new();
+        let l = leaves(3);
+        let tree = MerkleTree::from_leaves(&poseidon, 2, &l).unwrap();
+
+        let h01 = hash_node(&poseidon, &l[0], &l[1]);
+        let h23 = hash_node(&poseidon, &l[2], &Fr::zero());
+        assert_eq!(tree.root(), hash_node(&poseidon, &h01, &h23));
+
+        let mut incremental = MerkleTree::new(&poseidon, 2).unwrap();
+        for leaf in &l {
+            incremental.push(*leaf).unwrap();
+        }
+        assert_eq!(incremental.root(), tree.root());
+        incremental.push(Fr::one()).unwrap();
+        assert_eq!(incremental.push(Fr::one()), Err(PoseidonError::TreeFull));
+    }
+
+    #[test]
+    fn test_proof() {
+        let poseidon = Poseidon::new();
+        let l = leaves(5);
+        let tree = MerkleTree::from_leaves(&poseidon, 4, &l).unwrap();
+        let root = tree.root();
+        for (i, leaf) in l.iter().enumerate() {
+            let proof = tree.proof(i).unwrap();
+            assert!(proof.verify(&poseidon, &root, leaf));
+            assert!(!proof.verify(&poseidon, &root, &Fr::zero()));
+        }
+        // empty leaves can be proven as zero
+        let proof = tree.proof(10).unwrap();
+        assert!(proof.verify(&poseidon, &root, &Fr::zero()));
+    }
+
+    #[test]
+    fn test_set_many() {
+        let poseidon = Poseidon::new();
+        let l = leaves(9);
+        let mut tree = MerkleTree::from_leaves(&poseidon, 5, &l).unwrap();
+        let mut expected = MerkleTree::from_leaves(&poseidon, 5, &l).unwrap();
+
+        let updates = vec![(3, l[0]), (20, l[1]), (4, l[2]), (3, l[3])];
+        for (index, leaf) in &updates {
+            expected.set(*index, *leaf).unwrap();
+        }
+        tree.set_many(&updates).unwrap();
+        assert_eq!(tree.root(), expected.root());
+        assert_eq!(tree.len(), 21);
+        assert_eq!(tree.leaf(3), l[3]);
+
+        tree.set_many(&[(32, l[0])])
+            .expect_err("Leaf index out of range");
+    }
+
+    #[test]
+    fn test_sparse() {
+        let poseidon = Poseidon::new();
+        let mut tree = MerkleTree::new(&poseidon, 48).unwrap();
+        let index = 1 << 40;
+        tree.set(index, Fr::one()).unwrap();
+        assert_eq!(tree.len(), index + 1);
+        assert_eq!(tree.leaf(index), Fr::one());
+        assert!(tree.levels.iter().all(|l| l.len() == 1));
+
+        let proof = tree.proof(index).unwrap();
+        assert!(proof.verify(&poseidon, &tree.root(), &Fr::one()));
+
+        // setting it back to zero frees the nodes
+        tree.set(index, Fr::zero()).unwrap();
+        assert_eq!(tree.root(), MerkleTree::new(&poseidon, 48).unwrap().root());
+        assert!(tree.levels.iter().all(|l| l.is_empty()));
+        assert_eq!(
+            tree.set(1 << 48, Fr::one()),
+            Err(PoseidonError::IndexOutOfRange(1 << 48))
+        );
+    }
+}
@@ -0,0 +1,345 @@
+use alloc::{vec, vec::Vec};
+
+use crate::fr::*;
+
+use crate::{Fr, FrRepr, Poseidon, PoseidonError};
+
+// Domain tags placed in the capacity element, so that leaves, inner nodes
+// and the bagged root can never be confused with each other.
+pub const LEAF_DOMAIN: u64 = 1;
+pub const NODE_DOMAIN: u64 = 2;
+pub const ROOT_DOMAIN: u64 = 3;
+
+// Merkle Mountain Range: an append-only list of perfect binary trees (the
+// peaks), one for each bit set in the number of leaves, bigger ones first.
+pub struct MerkleMountainRange<'a> {
+    poseidon: &'a Poseidon,
+    // levels[h] holds the roots of all the complete subtrees of height h,
+    // from left to right
+    levels: Vec<Vec<Fr>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MmrProof {
+    pub index: usize,
+    pub size: usize,
+    // path from the leaf up to the peak containing it
+    pub siblings: Vec<Fr>,
+    pub peaks: Vec<Fr>,
+}
+
+// Proof that the MMR of new_size leaves extends the one of old_size leaves:
+// every old peak is a node of the new MMR, so it is enough to give the path
+// from each old peak up to the new peak containing it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MmrConsistencyProof {
+    pub old_size: usize,
+    pub new_size: usize,
+    pub old_peaks: Vec<Fr>,
+    pub new_peaks: Vec<Fr>,
+    pub paths: Vec<Vec<Fr>>,
+}
+
+fn domain(tag: u64) -> Fr {
+    Fr::from_repr(FrRepr::from(tag)).unwrap()
+}
+
+pub fn hash_leaf(poseidon: &Poseidon, leaf: &Fr) -> Fr {
+    poseidon
+        .hash_with_state(vec![*leaf], domain(LEAF_DOMAIN))
+        .unwrap()
+}
+
+pub fn hash_node(poseidon: &Poseidon, left: &Fr, right: &Fr) -> Fr {
+    poseidon
+        .hash_with_state(vec![*left, *right], domain(NODE_DOMAIN))
+        .unwrap()
+}
+
+// heights of the peaks of an MMR with size leaves, from left to right
+pub fn peak_heights(size: usize) -> Vec<usize> {
+    (0..usize::BITS as usize)
+        .rev()
+        .filter(|h| (size >> h) & 1 == 1)
+        .collect()
+}
+
+// position and height of the peak containing the given leaf
+fn covering_peak(size: usize, leaf: usize) -> Option<(usize, usize)> {
+    let mut start = 0;
+    for (i, h) in peak_heights(size).into_iter().enumerate() {
+        start += 1 << h;
+        if leaf < start {
+            return Some((i, h));
+        }
+    }
+    None
+}
+
+// Bags the peaks from right to left and binds the result to the size, which
+// is hashed as a field element in the first input of the root hash, the
+// capacity holding ROOT_DOMAIN as for any other root:
+// bagged = H(p0, H(p1, ... H(pn-1, pn)))
+// root = Poseidon::hash_with_state([size, bagged], ROOT_DOMAIN)
+// The empty MMR has root zero.
+pub fn bag_peaks(poseidon: &Poseidon, size: usize, peaks: &[Fr]) -> Fr {
+    let bagged = match peaks.split_last() {
+        Some((last, rest)) => rest
+            .iter()
+            .rev()
+            .fold(*last, |acc, peak| hash_node(poseidon, peak, &acc)),
+        None => return Fr::zero(),
+    };
+    poseidon
+        .hash_with_state(vec![Fr::from_u64(size as u64), bagged], domain(ROOT_DOMAIN))
+        .unwrap()
+}
+
+fn climb(poseidon: &Poseidon, node: &Fr, index: usize, siblings: &[Fr]) -> Fr {
+    let mut node = *node;
+    for (k, sibling) in siblings.iter().enumerate() {
+        node = if (index >> k) & 1 == 0 {
+            hash_node(poseidon, &node, sibling)
+        } else {
+            hash_node(poseidon, sibling, &node)
+        };
+    }
+    node
+}
+
+impl<'a> MerkleMountainRange<'a> {
+    pub fn new(poseidon: &'a Poseidon) -> MerkleMountainRange<'a> {
+        MerkleMountainRange {
+            poseidon,
+            levels: vec![Vec::new()],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.levels[0].is_empty()
+    }
+
+    pub fn poseidon(&self) -> &'a Poseidon {
+        self.poseidon
+    }
+
+    // appends a leaf and returns its index
+    pub fn append(&mut self, leaf: Fr) -> usize {
+        let index = self.len();
+        self.levels[0].push(hash_leaf(self.poseidon, &leaf));
+        // merge while the new node closes a pair
+        let mut h = 0;
+        while self.levels[h].len() & 1 == 0 {
+            let n = self.levels[h].len();
+            let parent = hash_node(
+                self.poseidon,
+                &self.levels[h][n - 2],
+                &self.levels[h][n - 1],
+            );
+            if self.levels.len() == h + 1 {
+                self.levels.push(Vec::new());
+            }
+            self.levels[h + 1].push(parent);
+            h += 1;
+        }
+        index
+    }
+
+    // peaks of the MMR as it was when it had size leaves
+    pub fn peaks_at(&self, size: usize) -> Result<Vec<Fr>, PoseidonError> {
+        if size > self.len() {
+            return Err(PoseidonError::InvalidSize(size));
+        }
+        Ok(peak_heights(size)
+            .into_iter()
+            .map(|h| self.levels[h][(size >> h) - 1])
+            .collect())
+    }
+
+    pub fn peaks(&self) -> Vec<Fr> {
+        self.peaks_at(self.len()).unwrap()
+    }
+
+    pub fn root_at(&self, size: usize) -> Result<Fr, PoseidonError> {
+        Ok(bag_peaks(self.poseidon, size, &self.peaks_at(size)?))
+    }
+
+    pub fn root(&self) -> Fr {
+        self.root_at(self.len()).unwrap()
+    }
+
+    // inclusion proof of a leaf against the root of the MMR with size leaves
+    pub fn proof_at(&self, index: usize, size: usize) -> Result<MmrProof, PoseidonError> {
+        if index >= size {
+            return Err(PoseidonError::IndexOutOfRange(index));
+        }
+        let peaks = self.peaks_at(size)?;
+        let (_, height) = covering_peak(size, index).unwrap();
+        let siblings = (0..height)
+            .map(|k| self.levels[k][(index >> k) ^ 1])
+            .collect();
+        Ok(MmrProof {
+            index,
+            size,
+            siblings,
+            peaks,
+        })
+    }
+
+    pub fn proof(&self, index: usize) -> Result<MmrProof, PoseidonError> {
+        self.proof_at(index, self.len())
+    }
+
+    pub fn consistency_proof(
+        &self,
+        old_size: usize,
+        new_size: usize,
+    ) -> Result<MmrConsistencyProof, PoseidonError> {
+        if old_size > new_size {
+            return Err(PoseidonError::InvalidSize(old_size));
+        }
+        let old_peaks = self.peaks_at(old_size)?;
+        let new_peaks = self.peaks_at(new_size)?;
+        let mut paths = Vec::new();
+        let mut start = 0;
+        for h in peak_heights(old_size) {
+            let (_, new_height) = covering_peak(new_size, start).unwrap();
+            let path = (h..new_height)
+                .map(|k| self.levels[k][(start >> k) ^ 1])
+                .collect();
+            paths.push(path);
+            start += 1 << h;
+        }
+        Ok(MmrConsistencyProof {
+            old_size,
+            new_size,
+            old_peaks,
+            new_peaks,
+            paths,
+        })
+    }
+}
+
+impl MmrProof {
+    pub fn verify(&self, poseidon: &Poseidon, root: &Fr, leaf: &Fr) -> bool {
+        let (i, height) = match covering_peak(self.size, self.index) {
+            Some(p) => p,
+            None => return false,
+        };
+        if self.peaks.len() != peak_heights(self.size).len() || self.siblings.len() != height {
+            return false;
+        }
+        let peak = climb(
+            poseidon,
+            &hash_leaf(poseidon, leaf),
+            self.index,
+            &self.siblings,
+        );
+        peak == self.peaks[i] && bag_peaks(poseidon, self.size, &self.peaks) == *root
+    }
+}
+
+impl MmrConsistencyProof {
+    pub fn verify(&self, poseidon: &Poseidon, old_root: &Fr, new_root: &Fr) -> bool {
+        let old_heights = peak_heights(self.old_size);
+        if self.old_size > self.new_size
+            || self.old_peaks.len() != old_heights.len()
+            || self.paths.len() != old_heights.len()
+            || self.new_peaks.len() != peak_heights(self.new_size).len()
+            || bag_peaks(poseidon, self.old_size, &self.old_peaks) != *old_root
+            || bag_peaks(poseidon, self.new_size, &self.new_peaks) != *new_root
+        {
+            return false;
+        }
+        let mut start = 0;
+        for ((h, peak), path) in old_heights.iter().zip(&self.old_peaks).zip(&self.paths) {
+            let (i, new_height) = covering_peak(self.new_size, start).unwrap();
+            if path.len() != new_height - h
+                || climb(poseidon, peak, start >> h, path) != self.new_peaks[i]
+            {
+                return false;
+            }
+            start += 1 << h;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_peaks() {
+        let poseidon = Poseidon::new();
+        let mut mmr = MerkleMountainRange::new(&poseidon);
+        for i in 0..7 {
+            assert_eq!(mmr.append(Fr::from_u64(i)), i as usize);
+        }
+        let l: Vec<Fr> = (0..7)
+            .map(|i| hash_leaf(&poseidon, &Fr::from_u64(i)))
+            .collect();
+        let n01 = hash_node(&poseidon, &l[0], &l[1]);
+        let n23 = hash_node(&poseidon, &l[2], &l[3]);
+        let n45 = hash_node(&poseidon, &l[4], &l[5]);
+        let peaks = vec![hash_node(&poseidon, &n01, &n23), n45, l[6]];
+        assert_eq!(mmr.peaks(), peaks);
+
+        let bagged = hash_node(
+            &poseidon,
+            &peaks[0],
+            &hash_node(&poseidon, &peaks[1], &peaks[2]),
+        );
+        let root = poseidon
+            .hash_with_state(vec![Fr::from_u64(7), bagged], Fr::from_u64(ROOT_DOMAIN))
+            .unwrap();
+        assert_eq!(mmr.root(), root);
+        assert_eq!(MerkleMountainRange::new(&poseidon).root(), Fr::zero());
+
+        // the same peaks with another size give another root
+        assert_ne!(bag_peaks(&poseidon, 8, &peaks), root);
+        assert_ne!(
+            poseidon
+                .hash_with_state(vec![bagged], Fr::from_u64(7))
+                .unwrap(),
+            root
+        );
+    }
+
+    #[test]
+    fn test_proofs() {
+        let poseidon = Poseidon::new();
+        let mut mmr = MerkleMountainRange::new(&poseidon);
+        for i in 0..11 {
+            mmr.append(Fr::from_u64(i));
+        }
+        let root = mmr.root();
+        for i in 0..11 {
+            let proof = mmr.proof(i).unwrap();
+            assert!(proof.verify(&poseidon, &root, &Fr::from_u64(i as u64)));
+            assert!(!proof.verify(&poseidon, &root, &Fr::from_u64(i as u64 + 1)));
+        }
+        // historical leaf against an older root
+        let old_root = mmr.root_at(6).unwrap();
+        let proof = mmr.proof_at(3, 6).unwrap();
+        assert!(proof.verify(&poseidon, &old_root, &Fr::from_u64(3)));
+        assert!(!proof.verify(&poseidon, &root, &Fr::from_u64(3)));
+        mmr.proof_at(6, 6).expect_err("Leaf index out of range");
+
+        for old_size in 0..=11 {
+            for new_size in old_size..=11 {
+                let proof = mmr.consistency_proof(old_size, new_size).unwrap();
+                let old_root = mmr.root_at(old_size).unwrap();
+                let new_root = mmr.root_at(new_size).unwrap();
+                assert!(proof.verify(&poseidon, &old_root, &new_root));
+            }
+        }
+        let proof = mmr.consistency_proof(5, 9).unwrap();
+        let other_root = mmr.root_at(4).unwrap();
+        assert!(!proof.verify(&poseidon, &other_root, &mmr.root_at(9).unwrap()));
+    }
+}
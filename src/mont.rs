@@ -0,0 +1,69 @@
+// BN254 scalar field arithmetic on little-endian limbs in Montgomery form
+// (R = 2^256), shared by build.rs to convert the constants and by the
+// core-only Fr of fr.rs. Included with include!, not a module, as build.rs
+// can't use the crate.
+
+// BN254 scalar field modulus, little-endian limbs
+const MODULUS: [u64; 4] = [
+    0x43e1f593f0000001,
+    0x2833e84879b97091,
+    0xb85045b68181585d,
+    0x30644e72e131a029,
+];
+// R^2 mod modulus
+const R2: [u64; 4] = [
+    0x1bb8e645ae216da7,
+    0x53fe3ab1e35c59e3,
+    0x8c49833d53bb8085,
+    0x0216d0b17f4e44a5,
+];
+// -modulus^-1 mod 2^64
+const INV: u64 = 0xc2e1f593efffffff;
+
+// a - b, returning the borrow
+fn sub(a: &mut [u64; 4], b: &[u64; 4]) -> bool {
+    let mut borrow = false;
+    for (a, b) in a.iter_mut().zip(b) {
+        let (d, b1) = a.overflowing_sub(*b);
+        let (d, b2) = d.overflowing_sub(borrow as u64);
+        *a = d;
+        borrow = b1 || b2;
+    }
+    borrow
+}
+
+fn lt_modulus(a: &[u64; 4]) -> bool {
+    a.iter().rev().lt(MODULUS.iter().rev())
+}
+
+// CIOS Montgomery multiplication, a * b / R mod modulus
+fn mont_mul(a: &[u64; 4], b: &[u64; 4]) -> [u64; 4] {
+    let mut t = [0u64; 6];
+    for bi in b {
+        let mut carry = 0u128;
+        for j in 0..4 {
+            let v = t[j] as u128 + a[j] as u128 * *bi as u128 + carry;
+            t[j] = v as u64;
+            carry = v >> 64;
+        }
+        let v = t[4] as u128 + carry;
+        t[4] = v as u64;
+        t[5] = (v >> 64) as u64;
+
+        let k = t[0].wrapping_mul(INV);
+        let mut carry = (t[0] as u128 + k as u128 * MODULUS[0] as u128) >> 64;
+        for j in 1..4 {
+            let v = t[j] as u128 + k as u128 * MODULUS[j] as u128 + carry;
+            t[j - 1] = v as u64;
+            carry = v >> 64;
+        }
+        let v = t[4] as u128 + carry;
+        t[3] = v as u64;
+        t[4] = t[5] + (v >> 64) as u64;
+    }
+    let mut r = [t[0], t[1], t[2], t[3]];
+    if t[4] != 0 || !lt_modulus(&r) {
+        sub(&mut r, &MODULUS);
+    }
+    r
+}
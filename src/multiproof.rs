@@ -0,0 +1,334 @@
+//! Compact Merkle multiproofs.
+//!
+//! A multiproof proves several leaves of the same tree at once, sending every
+//! sibling only once and omitting the nodes that the verifier can compute
+//! from the leaves themselves.
+//!
+//! Siblings are listed level by level, from the leaves up to the root, and
+//! inside each level by increasing node index. Starting with the sorted leaf
+//! indices, for each level and each known node index `i` (in increasing
+//! order): if `i ^ 1` is also known both nodes are hashed together, otherwise
+//! the next sibling of the proof is used as the node at `i ^ 1`. The parents
+//! `i >> 1` are the known nodes of the next level.
+//!
+//! Byte encoding (all integers little-endian):
+//!
+//! ```text
+//! depth        u8, so at most 255
+//! n_indices    u32
+//! indices      n_indices * u64, strictly increasing
+//! n_siblings   u32
+//! siblings     n_siblings * 32 bytes, field elements in little-endian
+//! ```
+//!
+//! Field elements must be canonical (smaller than the modulus), which is the
+//! same layout used by go-iden3-crypto and circomlibjs for serialized `Fr`.
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+use crate::merkle_tree::{hash_node, MerkleProof, MerkleTree};
+use crate::{Fr, Poseidon, PoseidonError};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MerkleMultiProof {
+    pub depth: usize,
+    pub indices: Vec<usize>,
+    pub siblings: Vec<Fr>,
+}
+
+fn sorted_indices(indices: &[usize], depth: usize) -> Result<Vec<usize>, PoseidonError> {
+    if indices.is_empty() {
+        return Err(PoseidonError::EmptyInput);
+    }
+    let mut sorted = indices.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+    if sorted.len() != indices.len() {
+        return Err(PoseidonError::DuplicateValue);
+    }
+    let last = sorted[sorted.len() - 1];
+    if last >> depth != 0 {
+        return Err(PoseidonError::IndexOutOfRange(last));
+    }
+    Ok(sorted)
+}
+
+impl MerkleTree<'_> {
+    pub fn multiproof(&self, indices: &[usize]) -> Result<MerkleMultiProof, PoseidonError> {
+        let depth = self.depth();
+        let sorted = sorted_indices(indices, depth)?;
+        let mut known = sorted.clone();
+        let mut siblings = Vec::new();
+        for k in 0..depth {
+            let mut next = Vec::with_capacity(known.len());
+            let mut i = 0;
+            while i < known.len() {
+                let index = known[i];
+                if i + 1 < known.len() && known[i + 1] == index ^ 1 {
+                    i += 2;
+                } else {
+                    siblings.push(self.node(k, index ^ 1));
+                    i += 1;
+                }
+                next.push(index >> 1);
+            }
+            known = next;
+        }
+        Ok(MerkleMultiProof {
+            depth,
+            indices: sorted,
+            siblings,
+        })
+    }
+}
+
+impl MerkleMultiProof {
+    // leaves must be given in the order of self.indices
+    pub fn compute_root(&self, poseidon: &Poseidon, leaves: &[Fr]) -> Result<Fr, PoseidonError> {
+        if self.depth == 0 || self.depth >= usize::BITS as usize {
+            return Err(PoseidonError::InvalidDepth(self.depth));
+        }
+        if leaves.len() != self.indices.len() {
+            return Err(PoseidonError::InvalidProof("Wrong number of leaves"));
+        }
+        let sorted = sorted_indices(&self.indices, self.depth)?;
+        if sorted != self.indices {
+            return Err(PoseidonError::InvalidProof("Leaf indices not sorted"));
+        }
+        let mut known: Vec<(usize, Fr)> = sorted.into_iter().zip(leaves.iter().copied()).collect();
+        let mut siblings = self.siblings.iter();
+        for _ in 0..self.depth {
+            let mut next = Vec::with_capacity(known.len());
+            let mut i = 0;
+            while i < known.len() {
+                let (index, node) = known[i];
+                let sibling = if i + 1 < known.len() && known[i + 1].0 == index ^ 1 {
+                    i += 2;
+                    known[i - 1].1
+                } else {
+                    i += 1;
+                    match siblings.next() {
+                        Some(s) => *s,
+                        None => return Err(PoseidonError::InvalidProof("Not enough siblings")),
+                    }
+                };
+                let parent = if index & 1 == 0 {
+                    hash_node(poseidon, &node, &sibling)
+                } else {
+                    hash_node(poseidon, &sibling, &node)
+                };
+                next.push((index >> 1, parent));
+            }
+            known = next;
+        }
+        if siblings.next().is_some() {
+            return Err(PoseidonError::InvalidProof("Too many siblings"));
+        }
+        Ok(known[0].1)
+    }
+
+    pub fn verify(&self, poseidon: &Poseidon, root: &Fr, leaves: &[Fr]) -> bool {
+        match self.compute_root(poseidon, leaves) {
+            Ok(r) => r == *root,
+            Err(_) => false,
+        }
+    }
+
+    // fails if a field does not fit in its encoding
+    pub fn to_bytes(&self) -> Result<Vec<u8>, PoseidonError> {
+        let depth =
+            u8::try_from(self.depth).map_err(|_| PoseidonError::InvalidDepth(self.depth))?;
+        let too_many = |_| PoseidonError::InvalidEncoding("Too many elements");
+        let mut b = Vec::with_capacity(9 + 8 * self.indices.len() + 32 * self.siblings.len());
+        b.push(depth);
+        b.extend_from_slice(
+            &u32::try_from(self.indices.len())
+                .map_err(too_many)?
+                .to_le_bytes(),
+        );
+        for index in &self.indices {
+            b.extend_from_slice(&(*index as u64).to_le_bytes());
+        }
+        b.extend_from_slice(
+            &u32::try_from(self.siblings.len())
+                .map_err(too_many)?
+                .to_le_bytes(),
+        );
+        for sibling in &self.siblings {
+            b.extend_from_slice(&sibling.to_bytes_le());
+        }
+        Ok(b)
+    }
+
+    pub fn from_bytes(b: &[u8]) -> Result<MerkleMultiProof, PoseidonError> {
+        let mut r = Reader { b, pos: 0 };
+        let depth = r.take(1)?[0] as usize;
+        let n_indices = r.len()?;
+        let mut indices = Vec::new();
+        for _ in 0..n_indices {
+            let mut v = [0u8; 8];
+            v.copy_from_slice(r.take(8)?);
+            let index = usize::try_from(u64::from_le_bytes(v))
+                .map_err(|_| PoseidonError::InvalidEncoding("Index does not fit in usize"))?;
+            indices.push(index);
+        }
+        let n_siblings = r.len()?;
+        let mut siblings = Vec::new();
+        for _ in 0..n_siblings {
+            siblings.push(Fr::from_slice_le(r.take(32)?)?);
+        }
+        if r.pos != b.len() {
+            return Err(PoseidonError::InvalidEncoding("Trailing bytes"));
+        }
+        Ok(MerkleMultiProof {
+            depth,
+            indices,
+            siblings,
+        })
+    }
+}
+
+struct Reader<'a> {
+    b: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], PoseidonError> {
+        if self.b.len() - self.pos < n {
+            return Err(PoseidonError::InvalidEncoding("Unexpected end of input"));
+        }
+        self.pos += n;
+        Ok(&self.b[self.pos - n..self.pos])
+    }
+
+    // a u32 length
+    fn len(&mut self) -> Result<usize, PoseidonError> {
+        let mut v = [0u8; 4];
+        v.copy_from_slice(self.take(4)?);
+        usize::try_from(u32::from_le_bytes(v))
+            .map_err(|_| PoseidonError::InvalidEncoding("Length does not fit in usize"))
+    }
+}
+
+// Verifies independent proofs of the same tree, computing every shared
+// node only once. Siblings that are also computed from another proof must
+// match the computed value.
+pub fn verify_batch(poseidon: &Poseidon, root: &Fr, proofs: &[MerkleProof], leaves: &[Fr]) -> bool {
+    if proofs.is_empty() || proofs.len() != leaves.len() {
+        return false;
+    }
+    let depth = proofs[0].siblings.len();
+    if depth == 0 || depth >= usize::BITS as usize {
+        return false;
+    }
+    let mut known: BTreeMap<usize, Fr> = BTreeMap::new();
+    for (proof, leaf) in proofs.iter().zip(leaves) {
+        if proof.siblings.len() != depth
+            || proof.index >> depth != 0
+            || known.insert(proof.index, *leaf).is_some_and(|l| l != *leaf)
+        {
+            return false;
+        }
+    }
+    for k in 0..depth {
+        let mut next = BTreeMap::new();
+        for proof in proofs {
+            let index = proof.index >> k;
+            if next.contains_key(&(index >> 1)) {
+                continue;
+            }
+            let node = known[&index];
+            let sibling = match known.get(&(index ^ 1)) {
+                Some(s) => {
+                    if *s != proof.siblings[k] {
+                        return false;
+                    }
+                    *s
+                }
+                None => proof.siblings[k],
+            };
+            let parent = if index & 1 == 0 {
+                hash_node(poseidon, &node, &sibling)
+            } else {
+                hash_node(poseidon, &sibling, &node)
+            };
+            next.insert(index >> 1, parent);
+        }
+        known = next;
+    }
+    known[&0] == *root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fr::*;
+
+    fn leaves(n: u64) -> Vec<Fr> {
+        (0..n).map(|i| Fr::from_u64(i + 1)).collect()
+    }
+
+    #[test]
+    fn test_multiproof() {
+        let poseidon = Poseidon::new();
+        let l = leaves(13);
+        let tree = MerkleTree::from_leaves(&poseidon, 4, &l).unwrap();
+        let root = tree.root();
+
+        let indices = vec![2, 3, 4, 9, 12];
+        let proof = tree.multiproof(&indices).unwrap();
+        let proven: Vec<Fr> = indices.iter().map(|i| l[*i]).collect();
+        assert!(proof.verify(&poseidon, &root, &proven));
+        // shared siblings are sent once
+        assert!(proof.siblings.len() < indices.len() * tree.depth());
+
+        let mut wrong = proven.clone();
+        wrong[3] = Fr::zero();
+        assert!(!proof.verify(&poseidon, &root, &wrong));
+
+        let bytes = proof.to_bytes().unwrap();
+        let decoded = MerkleMultiProof::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded, proof);
+        MerkleMultiProof::from_bytes(&bytes[1..]).expect_err("Trailing bytes");
+
+        let deep = MerkleMultiProof {
+            depth: 256,
+            ..proof.clone()
+        };
+        assert_eq!(deep.to_bytes(), Err(PoseidonError::InvalidDepth(256)));
+
+        tree.multiproof(&[1, 1]).expect_err("Duplicated leaf index");
+        assert_eq!(
+            tree.multiproof(&[16]),
+            Err(PoseidonError::IndexOutOfRange(16))
+        );
+
+        // proving all the leaves needs no siblings
+        let all: Vec<usize> = (0..16).collect();
+        let proof = tree.multiproof(&all).unwrap();
+        assert!(proof.siblings.is_empty());
+        let mut padded = l.clone();
+        padded.resize(16, Fr::zero());
+        assert!(proof.verify(&poseidon, &root, &padded));
+    }
+
+    #[test]
+    fn test_verify_batch() {
+        let poseidon = Poseidon::new();
+        let l = leaves(10);
+        let tree = MerkleTree::from_leaves(&poseidon, 4, &l).unwrap();
+        let root = tree.root();
+
+        let indices = [7, 0, 1, 6];
+        let proofs: Vec<MerkleProof> = indices.iter().map(|i| tree.proof(*i).unwrap()).collect();
+        let proven: Vec<Fr> = indices.iter().map(|i| l[*i]).collect();
+        assert!(verify_batch(&poseidon, &root, &proofs, &proven));
+
+        let mut wrong = proven.clone();
+        wrong[2] = Fr::zero();
+        assert!(!verify_batch(&poseidon, &root, &proofs, &wrong));
+    }
+}
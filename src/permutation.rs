@@ -0,0 +1,131 @@
+// The round loop shared by Poseidon, fixed::Poseidon, PoseidonHasher and
+// the SIMD lanes, so that they all apply the same schedule: each round adds
+// the round constants, raises the whole state (full rounds) or only its
+// first element (partial rounds) to the fifth power, and multiplies it by
+// the MDS matrix.
+use crate::fr::Field;
+
+use crate::field::{FfMul, FieldBackend, Mul};
+use crate::Fr;
+
+// element of the permutation state: a field element multiplied with M, or
+// one field element per lane for the SIMD implementation
+pub(crate) trait Element<M>: Copy {
+    type Constant;
+
+    fn zero() -> Self;
+    fn add_constant(&mut self, c: &Self::Constant);
+    fn pow5(&mut self);
+    // self += c * x
+    fn add_product(&mut self, c: &Self::Constant, x: &Self);
+}
+
+impl<M: Mul> Element<M> for Fr {
+    type Constant = Fr;
+
+    #[inline(always)]
+    fn zero() -> Fr {
+        <Fr as Field>::zero()
+    }
+
+    #[inline(always)]
+    fn add_constant(&mut self, c: &Fr) {
+        Field::add_assign(self, c);
+    }
+
+    #[inline(always)]
+    fn pow5(&mut self) {
+        let aux = *self;
+        M::square(self);
+        M::square(self);
+        M::mul_assign(self, &aux);
+    }
+
+    #[inline(always)]
+    fn add_product(&mut self, c: &Fr, x: &Fr) {
+        let mut aux = *c;
+        M::mul_assign(&mut aux, x);
+        Field::add_assign(self, &aux);
+    }
+}
+
+// whether the given round applies the sbox to the whole state
+#[inline(always)]
+pub(crate) fn is_full_round(round: usize, n_rounds_f: usize, n_rounds_p: usize) -> bool {
+    round < n_rounds_f / 2 || round >= n_rounds_f / 2 + n_rounds_p
+}
+
+// Applies the permutation of width t = state.len(). c holds the round
+// constants, t per round, and m the t * t MDS matrix row by row. scratch
+// must have length t, it is used to avoid allocating on each round.
+#[inline(always)]
+pub(crate) fn permute<M, E: Element<M>>(
+    state: &mut [E],
+    scratch: &mut [E],
+    c: &[E::Constant],
+    m: &[E::Constant],
+    n_rounds_f: usize,
+    n_rounds_p: usize,
+) {
+    let t = state.len();
+    for (round, c) in c.chunks_exact(t).take(n_rounds_f + n_rounds_p).enumerate() {
+        for (s, c) in state.iter_mut().zip(c) {
+            s.add_constant(c);
+        }
+
+        let n_sbox = if is_full_round(round, n_rounds_f, n_rounds_p) {
+            t
+        } else {
+            1
+        };
+        for s in state[..n_sbox].iter_mut() {
+            s.pow5();
+        }
+
+        for (n, row) in scratch.iter_mut().zip(m.chunks_exact(t)) {
+            *n = <E as Element<M>>::zero();
+            for (mij, s) in row.iter().zip(state.iter()) {
+                n.add_product(mij, s);
+            }
+        }
+        state.copy_from_slice(scratch);
+    }
+}
+
+// permutation over field elements, with the multiplication of backend
+#[inline(always)]
+pub(crate) fn permute_fr(
+    backend: FieldBackend,
+    state: &mut [Fr],
+    scratch: &mut [Fr],
+    c: &[Fr],
+    m: &[Fr],
+    n_rounds_f: usize,
+    n_rounds_p: usize,
+) {
+    match backend {
+        // safe as the backend is only set once it has been checked to be
+        // supported by the CPU
+        #[cfg(all(feature = "asm", target_os = "linux", target_arch = "x86_64"))]
+        FieldBackend::Asm => unsafe { permute_adx(state, scratch, c, m, n_rounds_f, n_rounds_p) },
+        #[cfg(all(feature = "asm", target_os = "linux", target_arch = "aarch64"))]
+        FieldBackend::Asm => {
+            permute::<crate::field::AsmMul, Fr>(state, scratch, c, m, n_rounds_f, n_rounds_p)
+        }
+        _ => permute::<FfMul, Fr>(state, scratch, c, m, n_rounds_f, n_rounds_p),
+    }
+}
+
+// compiled with BMI2 and ADX so that the multiplications are inlined
+#[cfg(all(feature = "asm", target_os = "linux", target_arch = "x86_64"))]
+#[target_feature(enable = "bmi2,adx")]
+unsafe fn permute_adx(
+    state: &mut [Fr],
+    scratch: &mut [Fr],
+    c: &[Fr],
+    m: &[Fr],
+    n_rounds_f: usize,
+    n_rounds_p: usize,
+) {
+    permute::<crate::field::AsmMul, Fr>(state, scratch, c, m, n_rounds_f, n_rounds_p)
+}
@@ -0,0 +1,368 @@
+// The R1CS of Poseidon::hash in the binary formats of circom, .r1cs
+// (version 1) and .wtns (version 2), with the witness computed natively, so
+// that it can be checked with snarkjs (snarkjs wtns check) without circom.
+//
+// The constraints follow the bellman gadget: ark and mix only update linear
+// combinations, each x^5 costs 3 constraints (x2 = x * x, x4 = x2 * x2,
+// x5 = x4 * x) and the output costs one more. The wires follow the circom
+// order: the constant 1, the hash (public output), the inputs (private) and
+// then the intermediate values, and each wire is its own label.
+use alloc::{vec, vec::Vec};
+
+use crate::fr::*;
+
+use crate::permutation;
+use crate::{Fr, Poseidon, PoseidonError};
+
+// (wire, coefficient) pairs, sorted by wire and with each wire at most once
+pub type Lc = Vec<(u32, Fr)>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Constraint {
+    pub a: Lc,
+    pub b: Lc,
+    pub c: Lc,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct R1cs {
+    pub n_wires: u32,
+    pub n_pub_out: u32,
+    pub n_pub_in: u32,
+    pub n_prv_in: u32,
+    pub constraints: Vec<Constraint>,
+}
+
+fn eval(lc: &Lc, witness: &[Fr]) -> Fr {
+    let mut r = Fr::zero();
+    for (wire, coeff) in lc {
+        let mut v = witness[*wire as usize];
+        v.mul_assign(coeff);
+        r.add_assign(&v);
+    }
+    r
+}
+
+fn modulus_le() -> [u8; 32] {
+    let mut b = [0u8; 32];
+    for (chunk, limb) in b.chunks_exact_mut(8).zip(Fr::char().0.iter()) {
+        chunk.copy_from_slice(&limb.to_le_bytes());
+    }
+    b
+}
+
+fn put_section(out: &mut Vec<u8>, section_type: u32, data: &[u8]) {
+    out.extend_from_slice(&section_type.to_le_bytes());
+    out.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    out.extend_from_slice(data);
+}
+
+impl R1cs {
+    pub fn is_satisfied(&self, witness: &[Fr]) -> bool {
+        witness.len() == self.n_wires as usize
+            && witness[0] == Fr::one()
+            && self.constraints.iter().all(|c| {
+                let mut ab = eval(&c.a, witness);
+                ab.mul_assign(&eval(&c.b, witness));
+                ab == eval(&c.c, witness)
+            })
+    }
+
+    // the .r1cs file
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut header = Vec::with_capacity(64);
+        header.extend_from_slice(&32u32.to_le_bytes());
+        header.extend_from_slice(&modulus_le());
+        for n in [self.n_wires, self.n_pub_out, self.n_pub_in, self.n_prv_in].iter() {
+            header.extend_from_slice(&n.to_le_bytes());
+        }
+        header.extend_from_slice(&(self.n_wires as u64).to_le_bytes());
+        header.extend_from_slice(&(self.constraints.len() as u32).to_le_bytes());
+
+        let mut constraints = Vec::new();
+        for c in &self.constraints {
+            for lc in [&c.a, &c.b, &c.c].iter() {
+                constraints.extend_from_slice(&(lc.len() as u32).to_le_bytes());
+                for (wire, coeff) in lc.iter() {
+                    constraints.extend_from_slice(&wire.to_le_bytes());
+                    constraints.extend_from_slice(&coeff.to_bytes_le());
+                }
+            }
+        }
+
+        let mut labels = Vec::with_capacity(8 * self.n_wires as usize);
+        for i in 0..self.n_wires as u64 {
+            labels.extend_from_slice(&i.to_le_bytes());
+        }
+
+        let mut out = Vec::new();
+        out.extend_from_slice(b"r1cs");
+        out.extend_from_slice(&1u32.to_le_bytes());
+        out.extend_from_slice(&3u32.to_le_bytes());
+        put_section(&mut out, 1, &header);
+        put_section(&mut out, 2, &constraints);
+        put_section(&mut out, 3, &labels);
+        out
+    }
+}
+
+// the .wtns file of a witness
+pub fn wtns_bytes(witness: &[Fr]) -> Vec<u8> {
+    let mut header = Vec::with_capacity(40);
+    header.extend_from_slice(&32u32.to_le_bytes());
+    header.extend_from_slice(&modulus_le());
+    header.extend_from_slice(&(witness.len() as u32).to_le_bytes());
+
+    let mut values = Vec::with_capacity(32 * witness.len());
+    for w in witness {
+        values.extend_from_slice(&w.to_bytes_le());
+    }
+
+    let mut out = Vec::new();
+    out.extend_from_slice(b"wtns");
+    out.extend_from_slice(&2u32.to_le_bytes());
+    out.extend_from_slice(&2u32.to_le_bytes());
+    put_section(&mut out, 1, &header);
+    put_section(&mut out, 2, &values);
+    out
+}
+
+// a linear combination together with its value
+#[derive(Clone)]
+struct Num {
+    lc: Lc,
+    value: Fr,
+}
+
+impl Num {
+    fn add_term(&mut self, wire: u32, coeff: Fr) {
+        match self.lc.binary_search_by_key(&wire, |(w, _)| *w) {
+            Ok(i) => self.lc[i].1.add_assign(&coeff),
+            Err(i) => self.lc.insert(i, (wire, coeff)),
+        }
+    }
+
+    // self += k * other
+    fn add_scaled(&mut self, other: &Num, k: &Fr) {
+        for (wire, coeff) in &other.lc {
+            let mut c = *coeff;
+            c.mul_assign(k);
+            self.add_term(*wire, c);
+        }
+        let mut v = other.value;
+        v.mul_assign(k);
+        self.value.add_assign(&v);
+    }
+}
+
+struct Builder {
+    witness: Vec<Fr>,
+    constraints: Vec<Constraint>,
+}
+
+impl Builder {
+    fn alloc(&mut self, value: Fr) -> Num {
+        self.witness.push(value);
+        Num {
+            lc: vec![((self.witness.len() - 1) as u32, Fr::one())],
+            value,
+        }
+    }
+
+    fn mul(&mut self, a: &Num, b: &Num) -> Num {
+        let mut value = a.value;
+        value.mul_assign(&b.value);
+        let c = self.alloc(value);
+        self.constraints.push(Constraint {
+            a: a.lc.clone(),
+            b: b.lc.clone(),
+            c: c.lc.clone(),
+        });
+        c
+    }
+
+    fn sbox(&mut self, x: &Num) -> Num {
+        let x2 = self.mul(x, x);
+        let x4 = self.mul(&x2, &x2);
+        self.mul(&x4, x)
+    }
+}
+
+impl Poseidon {
+    // the R1CS of hash with n_inputs inputs
+    pub fn r1cs(&self, n_inputs: usize) -> Result<R1cs, PoseidonError> {
+        self.synthesize(&vec![Fr::zero(); n_inputs])
+            .map(|(r1cs, _)| r1cs)
+    }
+
+    // the witness of r1cs(inputs.len()) for inputs
+    pub fn witness(&self, inputs: &[Fr]) -> Result<Vec<Fr>, PoseidonError> {
+        self.synthesize(inputs).map(|(_, witness)| witness)
+    }
+
+    fn synthesize(&self, inputs: &[Fr]) -> Result<(R1cs, Vec<Fr>), PoseidonError> {
+        self.check_inputs_len(inputs.len())?;
+        let t = inputs.len() + 1;
+        let n_rounds_f = self.constants.n_rounds_f;
+        let n_rounds_p = self.constants.n_rounds_p[t - 2];
+        let c = &self.constants.c[t - 2];
+        let m = &self.constants.m[t - 2];
+
+        let mut b = Builder {
+            witness: Vec::new(),
+            constraints: Vec::new(),
+        };
+        let one = b.alloc(Fr::one());
+        // the value of the output is set at the end
+        let out = b.alloc(Fr::zero());
+        let mut state = vec![Num {
+            lc: Vec::new(),
+            value: Fr::zero(),
+        }];
+        for x in inputs {
+            state.push(b.alloc(*x));
+        }
+
+        for i in 0..(n_rounds_f + n_rounds_p) {
+            for (j, s) in state.iter_mut().enumerate() {
+                s.add_scaled(&one, &c[i * t + j]);
+            }
+            let full = permutation::is_full_round(i, n_rounds_f, n_rounds_p);
+            let n_sbox = if full { t } else { 1 };
+            for s in state.iter_mut().take(n_sbox) {
+                *s = b.sbox(s);
+            }
+            state = m
+                .iter()
+                .map(|row| {
+                    let mut n = Num {
+                        lc: Vec::new(),
+                        value: Fr::zero(),
+                    };
+                    for (mij, s) in row.iter().zip(&state) {
+                        n.add_scaled(s, mij);
+                    }
+                    n
+                })
+                .collect();
+        }
+
+        b.witness[1] = state[0].value;
+        b.constraints.push(Constraint {
+            a: state[0].lc.clone(),
+            b: one.lc,
+            c: out.lc,
+        });
+        let r1cs = R1cs {
+            n_wires: b.witness.len() as u32,
+            n_pub_out: 1,
+            n_pub_in: 0,
+            n_prv_in: inputs.len() as u32,
+            constraints: b.constraints,
+        };
+        Ok((r1cs, b.witness))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u32_at(b: &[u8], i: usize) -> u32 {
+        let mut w = [0u8; 4];
+        w.copy_from_slice(&b[i..i + 4]);
+        u32::from_le_bytes(w)
+    }
+
+    fn u64_at(b: &[u8], i: usize) -> u64 {
+        let mut w = [0u8; 8];
+        w.copy_from_slice(&b[i..i + 8]);
+        u64::from_le_bytes(w)
+    }
+
+    #[test]
+    fn test_r1cs() {
+        let poseidon = Poseidon::new();
+        for n in [1usize, 2, 16].iter() {
+            let inp: Vec<Fr> = (1..=*n as u64).map(Fr::from_u64).collect();
+            let r1cs = poseidon.r1cs(*n).unwrap();
+            let mut witness = poseidon.witness(&inp).unwrap();
+
+            let t = n + 1;
+            let n_sbox = t * 8 + crate::N_ROUNDS_P[t - 2];
+            assert_eq!(r1cs.constraints.len(), 3 * n_sbox + 1);
+            assert_eq!(r1cs.n_wires as usize, 2 + n + 3 * n_sbox);
+            assert_eq!(witness[1], poseidon.hash(inp.clone()).unwrap());
+            assert_eq!(&witness[2..2 + n], &inp[..]);
+            assert!(r1cs.is_satisfied(&witness));
+
+            witness[1].add_assign(&Fr::one());
+            assert!(!r1cs.is_satisfied(&witness));
+        }
+        assert_eq!(poseidon.r1cs(0).err(), Some(PoseidonError::EmptyInput));
+        assert_eq!(
+            poseidon.witness(&[Fr::zero(); 17]).err(),
+            Some(PoseidonError::InputTooLong { max: 16 })
+        );
+    }
+
+    #[test]
+    fn test_files() {
+        let poseidon = Poseidon::new();
+        let r1cs = poseidon.r1cs(2).unwrap();
+        let b = r1cs.to_bytes();
+        assert_eq!(&b[0..4], b"r1cs");
+        assert_eq!(u32_at(&b, 4), 1);
+        assert_eq!(u32_at(&b, 8), 3);
+
+        // header
+        assert_eq!(u32_at(&b, 12), 1);
+        assert_eq!(u64_at(&b, 16), 64);
+        let h = &b[24..88];
+        assert_eq!(u32_at(h, 0), 32);
+        // p - 1 ends with 0 in little-endian
+        let mut p = Fr::zero();
+        p.sub_assign(&Fr::one());
+        let mut p = p.to_bytes_le();
+        p[0] += 1;
+        assert_eq!(&h[4..36], &p[..]);
+        assert_eq!(u32_at(h, 36), r1cs.n_wires);
+        assert_eq!((u32_at(h, 40), u32_at(h, 44), u32_at(h, 48)), (1, 0, 2));
+        assert_eq!(u64_at(h, 52), r1cs.n_wires as u64);
+        assert_eq!(u32_at(h, 60) as usize, r1cs.constraints.len());
+
+        // constraints, the first one is x2 = x * x for x = C[0], as the
+        // capacity is 0
+        assert_eq!(u32_at(&b, 88), 2);
+        let size = u64_at(&b, 92) as usize;
+        let cs = &b[100..100 + size];
+        assert_eq!(u32_at(cs, 0), 1);
+        assert_eq!(u32_at(cs, 4), 0);
+        assert_eq!(&cs[8..40], &poseidon.constants.c[1][0].to_bytes_le()[..]);
+
+        // wire labels
+        let labels = &b[100 + size..];
+        assert_eq!(u32_at(labels, 0), 3);
+        assert_eq!(u64_at(labels, 4), 8 * r1cs.n_wires as u64);
+        assert_eq!(labels.len(), 12 + 8 * r1cs.n_wires as usize);
+        assert_eq!(u64_at(labels, 12 + 8 * 5), 5);
+
+        let witness = poseidon
+            .witness(&[Fr::from_u64(1), Fr::from_u64(2)])
+            .unwrap();
+        let w = wtns_bytes(&witness);
+        assert_eq!(&w[0..4], b"wtns");
+        assert_eq!((u32_at(&w, 4), u32_at(&w, 8)), (2, 2));
+        assert_eq!((u32_at(&w, 12), u64_at(&w, 16)), (1, 40));
+        assert_eq!(u32_at(&w, 24), 32);
+        assert_eq!(u32_at(&w, 60), r1cs.n_wires);
+        assert_eq!(
+            (u32_at(&w, 64), u64_at(&w, 68)),
+            (2, 32 * witness.len() as u64)
+        );
+        assert_eq!(w.len(), 76 + 32 * witness.len());
+        // 1, then the hash
+        assert_eq!(&w[76..108], &Fr::one().to_bytes_le()[..]);
+        assert_eq!(&w[108..140], &witness[1].to_bytes_le()[..]);
+    }
+}
@@ -0,0 +1,291 @@
+// Serde support. In human readable formats Fr is serialized as a decimal
+// string by default, and as a 0x prefixed hex string with the Hex wrapper or
+// with #[serde(with = "poseidon_rs::serialize::hex")]. Deserialization takes
+// both strings, with the same strict rules as Fr::from_json, but not numbers
+// (use Fr::from_json for those). In binary formats, such as bincode, it is
+// serialized as its 32 byte little-endian encoding.
+//
+// Constants use the format of circomlib's poseidon_constants.json, with the
+// number of rounds as optional fields that default to the ones of this crate.
+// The number of round constants and the size of the matrices are checked
+// against the number of rounds of each width.
+use alloc::vec::Vec;
+use core::fmt;
+
+use serde::de::{self, Deserializer, Visitor};
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+
+use crate::convert::{Dec, Hex};
+use crate::{Constants, Fr, N_ROUNDS_F, N_ROUNDS_P};
+
+struct FrVisitor;
+
+impl<'de> Visitor<'de> for FrVisitor {
+    type Value = Fr;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "a decimal or 0x prefixed hex string, or 32 little-endian bytes"
+        )
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<Fr, E> {
+        let r = if s.starts_with("0x") {
+            Fr::parse_hex(s)
+        } else {
+            Fr::parse_dec(s)
+        };
+        r.map_err(E::custom)
+    }
+
+    fn visit_bytes<E: de::Error>(self, b: &[u8]) -> Result<Fr, E> {
+        Fr::from_slice_le(b).map_err(E::custom)
+    }
+}
+
+// text in human readable formats, little-endian bytes otherwise
+fn serialize_fr<S: Serializer>(
+    e: &Fr,
+    text: &dyn fmt::Display,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    if serializer.is_human_readable() {
+        serializer.collect_str(text)
+    } else {
+        serializer.serialize_bytes(&e.to_bytes_le())
+    }
+}
+
+impl Serialize for Fr {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Dec(*self).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Fr {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Fr, D::Error> {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(FrVisitor)
+        } else {
+            deserializer.deserialize_bytes(FrVisitor)
+        }
+    }
+}
+
+impl Serialize for Dec {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_fr(&self.0, self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Dec {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Dec, D::Error> {
+        Fr::deserialize(deserializer).map(Dec)
+    }
+}
+
+impl Serialize for Hex {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_fr(&self.0, self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Hex {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Hex, D::Error> {
+        Fr::deserialize(deserializer).map(Hex)
+    }
+}
+
+pub mod hex {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(e: &Fr, serializer: S) -> Result<S::Ok, S::Error> {
+        Hex(*e).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Fr, D::Error> {
+        Fr::deserialize(deserializer)
+    }
+}
+
+fn default_n_rounds_f() -> usize {
+    N_ROUNDS_F
+}
+
+fn default_n_rounds_p() -> Vec<usize> {
+    N_ROUNDS_P.to_vec()
+}
+
+#[derive(Serialize, Deserialize)]
+struct ConstantsJson {
+    #[serde(rename = "C")]
+    c: Vec<Vec<Hex>>,
+    #[serde(rename = "M")]
+    m: Vec<Vec<Vec<Hex>>>,
+    #[serde(default = "default_n_rounds_f")]
+    n_rounds_f: usize,
+    #[serde(default = "default_n_rounds_p")]
+    n_rounds_p: Vec<usize>,
+}
+
+impl Serialize for Constants {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let hex = |v: &Vec<Fr>| v.iter().map(|e| Hex(*e)).collect();
+        ConstantsJson {
+            c: self.c.iter().map(hex).collect(),
+            m: self.m.iter().map(|m| m.iter().map(hex).collect()).collect(),
+            n_rounds_f: self.n_rounds_f,
+            n_rounds_p: self.n_rounds_p.clone(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Constants {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Constants, D::Error> {
+        let j = ConstantsJson::deserialize(deserializer)?;
+        if j.c.len() != j.n_rounds_p.len() || j.m.len() != j.n_rounds_p.len() {
+            return Err(de::Error::custom("Wrong number of widths"));
+        }
+        for (i, (c, m)) in j.c.iter().zip(&j.m).enumerate() {
+            let t = i + 2;
+            if c.len() != (j.n_rounds_f + j.n_rounds_p[i]) * t
+                || m.len() != t
+                || m.iter().any(|row| row.len() != t)
+            {
+                return Err(de::Error::custom(format_args!(
+                    "Wrong number of constants for width {}",
+                    t
+                )));
+            }
+        }
+        let fr = |v: Vec<Hex>| v.into_iter().map(|e| e.0).collect();
+        Ok(Constants {
+            c: j.c.into_iter().map(fr).collect(),
+            m: j.m
+                .into_iter()
+                .map(|m| m.into_iter().map(fr).collect())
+                .collect(),
+            n_rounds_f: j.n_rounds_f,
+            n_rounds_p: j.n_rounds_p,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+    use super::*;
+    use crate::{load_constants, Poseidon};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Vector {
+        inputs: Vec<Fr>,
+        #[serde(with = "hex")]
+        output: Fr,
+    }
+
+    #[test]
+    fn test_fr() {
+        let v = Vector {
+            inputs: vec![Fr::from_u64(1), Fr::from_u64(2)],
+            output: Poseidon::new()
+                .hash(vec![Fr::from_u64(1), Fr::from_u64(2)])
+                .unwrap(),
+        };
+        let s = serde_json::to_string(&v).unwrap();
+        assert_eq!(
+            s,
+            r#"{"inputs":["1","2"],"output":"0x115cc0f5e7d690413df64c6b9662e9cf2a3617f2743245519e19607a4417189a"}"#
+        );
+        assert_eq!(serde_json::from_str::<Vector>(&s).unwrap(), v);
+
+        // hex and decimal inputs are accepted, numbers and non canonical
+        // values are not
+        let v2: Vector = serde_json::from_str(
+            r#"{"inputs":["1","0x2"],"output":"7853200120776062878684798364095072458815029376092732009249414926327459813530"}"#,
+        )
+        .unwrap();
+        assert_eq!(v2, v);
+        serde_json::from_str::<Fr>("1").expect_err("Number");
+        serde_json::from_str::<Fr>(
+            r#""21888242871839275222246405745257275088548364400416034343698204186575808495619""#,
+        )
+        .expect_err("Non canonical field element");
+        serde_json::from_str::<Fr>(r#""01""#).expect_err("Leading zeros");
+
+        // binary formats use the 32 byte little-endian encoding
+        let b = bincode::serialize(&v).unwrap();
+        assert_eq!(b.len(), 8 + 2 * (8 + 32) + 8 + 32);
+        assert_eq!(bincode::deserialize::<Vector>(&b).unwrap(), v);
+        let b = bincode::serialize(&Hex(v.output)).unwrap();
+        assert_eq!(b[8..], v.output.to_bytes_le());
+        let mut p = vec![32, 0, 0, 0, 0, 0, 0, 0];
+        p.extend_from_slice(&[0xff; 32]);
+        bincode::deserialize::<Fr>(&p).expect_err("Non canonical field element");
+    }
+
+    #[test]
+    fn test_constants() {
+        let constants = load_constants().unwrap();
+        let s = serde_json::to_string(&constants).unwrap();
+        let decoded: Constants = serde_json::from_str(&s).unwrap();
+        assert_eq!(decoded.c, constants.c);
+        assert_eq!(decoded.m, constants.m);
+        assert_eq!(decoded.n_rounds_f, constants.n_rounds_f);
+        assert_eq!(decoded.n_rounds_p, constants.n_rounds_p);
+
+        // circomlib's poseidon_constants.json has only C and M, and may
+        // have other fields
+        let mut v: serde_json::Value = serde_json::from_str(&s).unwrap();
+        let obj = v.as_object_mut().unwrap();
+        obj.remove("n_rounds_f");
+        obj.remove("n_rounds_p");
+        obj.insert("S".to_string(), serde_json::Value::Array(vec![]));
+        let decoded: Constants = serde_json::from_value(v.clone()).unwrap();
+        assert_eq!(decoded.c, constants.c);
+        assert_eq!(decoded.n_rounds_p, constants.n_rounds_p);
+        assert_eq!(
+            v["C"][0][0],
+            "0x09c46e9ec68e9bd4fe1faaba294cba38a71aa177534cdd1b6c7dc0dbd0abd7a7"
+        );
+
+        // inner lengths must match the number of rounds of each width
+        let mut w = v.clone();
+        w["C"][3].as_array_mut().unwrap().pop();
+        let e = serde_json::from_value::<Constants>(w).unwrap_err();
+        assert_eq!(e.to_string(), "Wrong number of constants for width 5");
+        let mut w = v.clone();
+        w["M"][0][1].as_array_mut().unwrap().pop();
+        serde_json::from_value::<Constants>(w).expect_err("Wrong matrix");
+        let mut w = v.clone();
+        w["M"][15].as_array_mut().unwrap().pop();
+        serde_json::from_value::<Constants>(w).expect_err("Wrong matrix");
+        let mut w = v.clone();
+        w["n_rounds_p"] = serde_json::json!(vec![57; 16]);
+        serde_json::from_value::<Constants>(w).expect_err("Wrong rounds");
+
+        v["C"].as_array_mut().unwrap().pop();
+        serde_json::from_value::<Constants>(v).expect_err("Wrong constants");
+
+        let b = bincode::serialize(&constants).unwrap();
+        let decoded: Constants = bincode::deserialize(&b).unwrap();
+        assert_eq!(decoded.c, constants.c);
+        assert_eq!(decoded.m, constants.m);
+    }
+
+    // compares with circomlib's own src/poseidon_constants.json, which CI
+    // fetches at a fixed tag, with the path in CIRCOMLIB_POSEIDON_CONSTANTS
+    #[test]
+    #[ignore]
+    fn test_circomlib_constants() {
+        let path = std::env::var("CIRCOMLIB_POSEIDON_CONSTANTS").unwrap();
+        let json = std::fs::read_to_string(path).unwrap();
+        let constants: Constants = serde_json::from_str(&json).unwrap();
+        let builtin = load_constants().unwrap();
+        assert_eq!(constants.c, builtin.c);
+        assert_eq!(constants.m, builtin.m);
+        assert_eq!(constants.n_rounds_p, builtin.n_rounds_p);
+    }
+}
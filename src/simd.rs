@@ -0,0 +1,415 @@
+// Multi-lane Poseidon: runs 4 or 8 independent permutations at once.
+//
+// Field elements of all the lanes are stored interleaved by limb, using 32
+// bit limbs held in u64 words, so that the Montgomery multiplications in
+// sbox and mix become lane-wise 32x32->64 multiplications that the compiler
+// turns into vpmuludq when the code is compiled for AVX2 or AVX-512. The
+// CPU features are detected at runtime, falling back to the scalar
+// permutation when they are not available. Fr is kept in Montgomery form
+// with R = 2^256, which is the same R for 8 limbs of 32 bits as for 4 limbs
+// of 64 bits, so the raw representation is used as is.
+use crate::fr::*;
+
+use crate::fixed;
+use crate::permutation;
+use crate::{Fr, FrRepr, PoseidonError};
+
+const LIMBS: usize = 8;
+const MASK: u64 = 0xffff_ffff;
+
+// BN254 scalar field modulus, 32 bit limbs in little-endian order
+const MODULUS: [u64; LIMBS] = [
+    0xf0000001, 0x43e1f593, 0x79b97091, 0x2833e848, 0x8181585d, 0xb85045b6, 0xe131a029, 0x30644e72,
+];
+
+// -MODULUS^-1 mod 2^32
+const INV: u64 = inv(MODULUS[0]);
+
+const fn inv(m0: u64) -> u64 {
+    let mut inv: u64 = 1;
+    let mut i = 0;
+    while i < 31 {
+        inv = inv.wrapping_mul(inv) & MASK;
+        inv = inv.wrapping_mul(m0) & MASK;
+        i += 1;
+    }
+    inv.wrapping_neg() & MASK
+}
+
+type Limbs = [u64; LIMBS];
+
+fn to_limbs(x: &Fr) -> Limbs {
+    let repr = x.into_raw_repr();
+    let mut limbs = [0u64; LIMBS];
+    for (i, w) in repr.as_ref().iter().enumerate() {
+        limbs[2 * i] = w & MASK;
+        limbs[2 * i + 1] = w >> 32;
+    }
+    limbs
+}
+
+fn from_limbs(limbs: &Limbs) -> Fr {
+    let mut repr = FrRepr::from(0);
+    for (i, w) in repr.as_mut().iter_mut().enumerate() {
+        *w = limbs[2 * i] | (limbs[2 * i + 1] << 32);
+    }
+    Fr::from_raw_repr(repr).unwrap()
+}
+
+#[derive(Clone, Copy)]
+struct Lanes<const L: usize>([[u64; L]; LIMBS]);
+
+impl<const L: usize> Lanes<L> {
+    // subtracts the modulus from the lanes that are not reduced
+    #[inline(always)]
+    fn reduce(mut r: [[u64; L]; LIMBS], carry: [u64; L]) -> Self {
+        let mut s = [[0u64; L]; LIMBS];
+        let mut borrow = [0u64; L];
+        for j in 0..LIMBS {
+            for l in 0..L {
+                let x = r[j][l].wrapping_sub(MODULUS[j] + borrow[l]);
+                s[j][l] = x & MASK;
+                borrow[l] = x >> 63;
+            }
+        }
+        for l in 0..L {
+            let mask = 0u64.wrapping_sub(carry[l] | (borrow[l] ^ 1));
+            for j in 0..LIMBS {
+                r[j][l] = (s[j][l] & mask) | (r[j][l] & !mask);
+            }
+        }
+        Lanes(r)
+    }
+
+    #[inline(always)]
+    fn add_by(&self, b: impl Fn(usize, usize) -> u64) -> Self {
+        let mut r = [[0u64; L]; LIMBS];
+        let mut carry = [0u64; L];
+        for (j, rj) in r.iter_mut().enumerate() {
+            for l in 0..L {
+                let x = self.0[j][l] + b(j, l) + carry[l];
+                rj[l] = x & MASK;
+                carry[l] = x >> 32;
+            }
+        }
+        Self::reduce(r, carry)
+    }
+
+    // CIOS Montgomery multiplication
+    #[inline(always)]
+    fn mul_by(&self, b: impl Fn(usize, usize) -> u64) -> Self {
+        let a = &self.0;
+        let mut t = [[0u64; L]; LIMBS + 2];
+        for i in 0..LIMBS {
+            let mut c = [0u64; L];
+            for j in 0..LIMBS {
+                for l in 0..L {
+                    let x = t[j][l] + a[j][l] * b(i, l) + c[l];
+                    t[j][l] = x & MASK;
+                    c[l] = x >> 32;
+                }
+            }
+            for l in 0..L {
+                let x = t[LIMBS][l] + c[l];
+                t[LIMBS][l] = x & MASK;
+                t[LIMBS + 1][l] = x >> 32;
+            }
+            let mut m = [0u64; L];
+            for l in 0..L {
+                m[l] = (t[0][l] * INV) & MASK;
+                c[l] = (t[0][l] + m[l] * MODULUS[0]) >> 32;
+            }
+            for j in 1..LIMBS {
+                for l in 0..L {
+                    let x = t[j][l] + m[l] * MODULUS[j] + c[l];
+                    t[j - 1][l] = x & MASK;
+                    c[l] = x >> 32;
+                }
+            }
+            for l in 0..L {
+                let x = t[LIMBS][l] + c[l];
+                t[LIMBS - 1][l] = x & MASK;
+                t[LIMBS][l] = t[LIMBS + 1][l] + (x >> 32);
+            }
+        }
+        let mut r = [[0u64; L]; LIMBS];
+        r.copy_from_slice(&t[..LIMBS]);
+        Self::reduce(r, t[LIMBS])
+    }
+
+    #[inline(always)]
+    fn add(&self, b: &Self) -> Self {
+        self.add_by(|j, l| b.0[j][l])
+    }
+
+    #[inline(always)]
+    fn add_const(&self, b: &Limbs) -> Self {
+        self.add_by(|j, _| b[j])
+    }
+
+    #[inline(always)]
+    fn mul(&self, b: &Self) -> Self {
+        self.mul_by(|i, l| b.0[i][l])
+    }
+
+    #[inline(always)]
+    fn mul_const(&self, b: &Limbs) -> Self {
+        self.mul_by(|i, _| b[i])
+    }
+}
+
+// the lanes do their own multiplication, so M is unused
+impl<const L: usize> permutation::Element<()> for Lanes<L> {
+    type Constant = Limbs;
+
+    #[inline(always)]
+    fn zero() -> Self {
+        Lanes([[0u64; L]; LIMBS])
+    }
+
+    #[inline(always)]
+    fn add_constant(&mut self, c: &Limbs) {
+        *self = self.add_const(c);
+    }
+
+    #[inline(always)]
+    fn pow5(&mut self) {
+        let x2 = self.mul(self);
+        let x4 = x2.mul(&x2);
+        *self = x4.mul(self);
+    }
+
+    #[inline(always)]
+    fn add_product(&mut self, c: &Limbs, x: &Self) {
+        *self = self.add(&x.mul_const(c));
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Backend {
+    Scalar,
+    Avx2,
+    Avx512,
+}
+
+impl Backend {
+    pub fn detect() -> Backend {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("avx512f") {
+                return Backend::Avx512;
+            }
+            if is_x86_feature_detected!("avx2") {
+                return Backend::Avx2;
+            }
+        }
+        Backend::Scalar
+    }
+
+    pub fn is_supported(&self) -> bool {
+        match self {
+            Backend::Scalar => true,
+            Backend::Avx2 => Backend::detect() != Backend::Scalar,
+            Backend::Avx512 => Backend::detect() == Backend::Avx512,
+        }
+    }
+
+    pub fn lanes(&self) -> usize {
+        match self {
+            Backend::Scalar => 1,
+            Backend::Avx2 => 4,
+            Backend::Avx512 => 8,
+        }
+    }
+}
+
+pub struct PoseidonLanes<const T: usize> {
+    scalar: fixed::Poseidon<T>,
+    // the constants of scalar, split in 32 bit limbs
+    c: Vec<Limbs>,
+    m: Vec<Limbs>,
+    backend: Backend,
+}
+
+impl<const T: usize> PoseidonLanes<T> {
+    pub fn new() -> Result<PoseidonLanes<T>, PoseidonError> {
+        Ok(Self::from_poseidon(fixed::Poseidon::new()?))
+    }
+
+    pub fn from_poseidon(scalar: fixed::Poseidon<T>) -> PoseidonLanes<T> {
+        PoseidonLanes {
+            c: scalar.c.iter().map(to_limbs).collect(),
+            m: scalar.m.iter().map(to_limbs).collect(),
+            scalar,
+            backend: Backend::detect(),
+        }
+    }
+
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    pub fn with_backend(mut self, backend: Backend) -> Result<PoseidonLanes<T>, PoseidonError> {
+        if !backend.is_supported() {
+            return Err(PoseidonError::UnsupportedBackend);
+        }
+        self.backend = backend;
+        Ok(self)
+    }
+
+    #[inline(always)]
+    fn permute<const L: usize>(&self, state: &mut [Lanes<L>; T]) {
+        let mut scratch = [<Lanes<L> as permutation::Element<()>>::zero(); T];
+        permutation::permute::<(), _>(
+            state,
+            &mut scratch,
+            &self.c,
+            &self.m,
+            self.scalar.n_rounds_f,
+            self.scalar.n_rounds_p,
+        );
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx2")]
+    unsafe fn permute_avx2<const L: usize>(&self, state: &mut [Lanes<L>; T]) {
+        self.permute(state)
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx512f")]
+    unsafe fn permute_avx512<const L: usize>(&self, state: &mut [Lanes<L>; T]) {
+        self.permute(state)
+    }
+
+    fn permute_lanes<const L: usize>(&self, states: &mut [[Fr; T]; L]) {
+        if self.backend == Backend::Scalar {
+            for state in states.iter_mut() {
+                self.scalar.permute(state);
+            }
+            return;
+        }
+        let mut lanes = [<Lanes<L> as permutation::Element<()>>::zero(); T];
+        for (l, state) in states.iter().enumerate() {
+            for (i, x) in state.iter().enumerate() {
+                for (limb, w) in lanes[i].0.iter_mut().zip(&to_limbs(x)) {
+                    limb[l] = *w;
+                }
+            }
+        }
+        match self.backend {
+            // the backend is only set once the CPU features have been checked
+            #[cfg(target_arch = "x86_64")]
+            Backend::Avx2 => unsafe { self.permute_avx2(&mut lanes) },
+            #[cfg(target_arch = "x86_64")]
+            Backend::Avx512 => unsafe { self.permute_avx512(&mut lanes) },
+            _ => self.permute(&mut lanes),
+        }
+        for (l, state) in states.iter_mut().enumerate() {
+            for (i, x) in state.iter_mut().enumerate() {
+                let mut limbs = [0u64; LIMBS];
+                for (w, limb) in limbs.iter_mut().zip(&lanes[i].0) {
+                    *w = limb[l];
+                }
+                *x = from_limbs(&limbs);
+            }
+        }
+    }
+
+    pub fn permute_x4(&self, states: &mut [[Fr; T]; 4]) {
+        self.permute_lanes(states)
+    }
+
+    pub fn permute_x8(&self, states: &mut [[Fr; T]; 8]) {
+        self.permute_lanes(states)
+    }
+
+    fn hash_chunk<const L: usize, I: AsRef<[Fr]>>(&self, inputs: &[I], hashes: &mut Vec<Fr>) {
+        let mut states = [[Fr::zero(); T]; L];
+        for (state, inp) in states.iter_mut().zip(inputs) {
+            state[1..].copy_from_slice(inp.as_ref());
+        }
+        self.permute_lanes(&mut states);
+        hashes.extend(states.iter().map(|state| state[0]));
+    }
+
+    // Hashes each input (of T - 1 elements) using as many lanes as the
+    // backend has, giving the same output as fixed::Poseidon::hash.
+    pub fn hash_many<I: AsRef<[Fr]>>(&self, inputs: &[I]) -> Result<Vec<Fr>, PoseidonError> {
+        if inputs.iter().any(|inp| inp.as_ref().len() != T - 1) {
+            return Err(PoseidonError::WrongInputsLength { expected: T - 1 });
+        }
+        let lanes = self.backend.lanes();
+        let mut hashes = Vec::with_capacity(inputs.len());
+        let mut chunks = inputs.chunks_exact(lanes);
+        for chunk in &mut chunks {
+            match lanes {
+                8 => self.hash_chunk::<8, I>(chunk, &mut hashes),
+                4 => self.hash_chunk::<4, I>(chunk, &mut hashes),
+                _ => hashes.push(self.scalar.hash(chunk[0].as_ref())?),
+            }
+        }
+        for inp in chunks.remainder() {
+            hashes.push(self.scalar.hash(inp.as_ref())?);
+        }
+        Ok(hashes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_modulus() {
+        let mut limbs = [0u64; LIMBS];
+        for (i, w) in Fr::char().as_ref().iter().enumerate() {
+            limbs[2 * i] = w & MASK;
+            limbs[2 * i + 1] = w >> 32;
+        }
+        assert_eq!(limbs, MODULUS);
+        assert_eq!(MODULUS[0].wrapping_mul(INV) & MASK, MASK);
+    }
+
+    #[test]
+    fn test_lanes() {
+        let scalar = fixed::Poseidon::<3>::new().unwrap();
+        let mut backends = vec![Backend::Scalar];
+        if Backend::detect() != Backend::Scalar {
+            backends.push(Backend::Avx2);
+        }
+        if Backend::detect() == Backend::Avx512 {
+            backends.push(Backend::Avx512);
+        }
+        let inputs: Vec<[Fr; 2]> = (0..11)
+            .map(|i| [Fr::from_u64(i), Fr::from_u64(1000 - i)])
+            .collect();
+        let expected: Vec<Fr> = inputs.iter().map(|inp| scalar.hash(inp).unwrap()).collect();
+
+        for backend in backends {
+            let p = PoseidonLanes::<3>::new()
+                .unwrap()
+                .with_backend(backend)
+                .unwrap();
+            assert_eq!(p.hash_many(&inputs).unwrap(), expected);
+
+            let mut states = [[Fr::zero(); 3]; 8];
+            for (state, inp) in states.iter_mut().zip(&inputs) {
+                state[1..].copy_from_slice(inp);
+            }
+            p.permute_x8(&mut states);
+            for (state, h) in states.iter().zip(&expected) {
+                assert_eq!(state[0], *h);
+            }
+            let mut states4 = [[Fr::zero(), Fr::from_u64(1), Fr::from_u64(2)]; 4];
+            p.permute_x4(&mut states4);
+            assert_eq!(
+                states4[3][0].to_string(),
+                "Fr(0x115cc0f5e7d690413df64c6b9662e9cf2a3617f2743245519e19607a4417189a)"
+            );
+
+            p.hash_many(&[[Fr::from_u64(1)]])
+                .expect_err("Wrong inputs length");
+        }
+    }
+}
@@ -0,0 +1,303 @@
+// Hashing of inputs of any length, compatible with SpongeHash and HashBytes
+// (and their X variants) from go-iden3-crypto.
+//
+// The inputs are hashed in frames of frame_size elements: when a frame is
+// full it is hashed, and the next frame starts with that hash followed by the
+// next inputs. A partially filled frame at the end is padded with zeros and
+// hashed.
+//
+// Byte messages are split into chunks of 31 bytes, each one read as a
+// big-endian integer, with the last chunk padded with zeros on the right up
+// to 31 bytes, and the chunks are sponge hashed.
+//
+// The only difference with go-iden3-crypto is the empty input, for which Go
+// returns a nil hash and no error, while here it is an EmptyInput error.
+use alloc::{vec, vec::Vec};
+
+use crate::fr::*;
+
+use crate::{Fr, Poseidon, PoseidonError};
+
+const CHUNK_SIZE: usize = 31;
+const FRAME_SIZE: usize = 16;
+
+fn chunk_to_fr(chunk: &[u8]) -> Fr {
+    let mut b = [0u8; 32];
+    b[1..1 + chunk.len()].copy_from_slice(chunk);
+    // 31 bytes are always smaller than the modulus
+    Fr::from_bytes_be(&b).unwrap()
+}
+
+impl Poseidon {
+    pub fn hash_bytes(&self, msg: &[u8]) -> Result<Fr, PoseidonError> {
+        self.hash_bytes_x(msg, FRAME_SIZE)
+    }
+
+    pub fn hash_bytes_x(&self, msg: &[u8], frame_size: usize) -> Result<Fr, PoseidonError> {
+        let inp: Vec<Fr> = msg.chunks(CHUNK_SIZE).map(chunk_to_fr).collect();
+        self.sponge_hash(&inp, frame_size)
+    }
+
+    // Hashes any number of elements, compatible with SpongeHash (with a
+    // frame_size of 16) and SpongeHashX from go-iden3-crypto.
+    pub fn sponge_hash(&self, inp: &[Fr], frame_size: usize) -> Result<Fr, PoseidonError> {
+        if !(2..=FRAME_SIZE).contains(&frame_size) {
+            return Err(PoseidonError::UnsupportedFrameSize(frame_size));
+        }
+        if inp.is_empty() {
+            return Err(PoseidonError::EmptyInput);
+        }
+        let mut frame = vec![Fr::zero(); frame_size];
+        let mut hash = Fr::zero();
+        let mut k = 0;
+        let mut dirty = false;
+        for x in inp {
+            frame[k] = *x;
+            dirty = true;
+            if k == frame_size - 1 {
+                hash = self.hash(frame.clone())?;
+                dirty = false;
+                frame[0] = hash;
+                for f in frame[1..].iter_mut() {
+                    *f = Fr::zero();
+                }
+                k = 1;
+            } else {
+                k += 1;
+            }
+        }
+        if dirty {
+            hash = self.hash(frame)?;
+        }
+        Ok(hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Expected outputs of Hash from go-iden3-crypto, poseidon/poseidon_test.go
+    // (TestPoseidonHash). SpongeHashX and HashBytesX hash a single frame as
+    // the zero padded frame, so they give these outputs for the inputs below.
+    const GO_1_2_T5: &str =
+        "1018317224307729531995786483840663576608797660851238720571059489595066344487";
+    const GO_1_2_T6: &str =
+        "15336558801450556532856248569924170992202208561737609669134139141992924267169";
+    const GO_3_4_T5: &str =
+        "5811595552068139067952687508729883632420015185677766880877743348592482390548";
+    const GO_3_4_T6: &str =
+        "12263118664590987767234828103155242843640892839966517009184493198782366909018";
+    const GO_1_9_T14: &str =
+        "5540388656744764564518487011617040650780060800286365721923524861648744699539";
+    const GO_1_14_T14: &str =
+        "8354478399926161176778659061636406690034081872658507739535256090879947077494";
+    const GO_1_9_T16: &str =
+        "11882816200654282475720830292386643970958445617880627439994635298904836126497";
+    const GO_1_16_T16: &str =
+        "9989051620750914585850546081941653841776809718687451684622678807385399211877";
+
+    // HashBytesX of the bytes 0..100 (three chunks of 31 bytes and one of 7),
+    // computed with light-poseidon 0.2, an independent implementation with
+    // the circomlib parameters, chaining the frames as Go does. Go itself is
+    // checked against the CLI in CI, with the vectors printed by tests/go.
+    const LP_BYTES_100_F2: &str =
+        "14088714289446122244497285897506087795076945961928259563126138658935696442167";
+    const LP_BYTES_100_F3: &str =
+        "17095834047071431275632566823777812934361164593118597948767554573334603835887";
+
+    // SpongeHashX of 1..=n, for each n, computed with light-poseidon 0.2 as
+    // the outputs above. It only has the circomlib parameters of up to 12
+    // inputs, so frames of 12 elements are the biggest checked here.
+    const LP_SPONGE_F3: [&str; 5] = [
+        "16319005924338521988144249782199320915969277491928916027259324394544057385749",
+        "13831821852403126897479426070347226427183075710625481252219866028995538813194",
+        "6542985608222806190361240322586112750744169038454362455181422643027100751666",
+        "16582777472462173091361456620964208283308194923479220921488330670920232907373",
+        "2471372894682600937379875366137951766263546689296527096431777319482417487166",
+    ];
+    const LP_SPONGE_F12: [&str; 33] = [
+        "6586661585933131793059731626842022986845240111276279274682189327279372956081",
+        "627507885746266413299921176841722625515699156866251057623645797306527186726",
+        "21821325008290439322973031473131992832348363079216810327989983242435194560414",
+        "19118597633779732666873256883704668544443254732787254852662181755880468556855",
+        "11502178284216104154497699107845372710389671895721277869445785957568713413800",
+        "21118186781228906296153797558027490788730397864816242948443906143398135873914",
+        "7605932163183692085168509937214296644381140817535749516959383819593238901935",
+        "17526454685255095153939325147198613526013025133532064389751080785729226490410",
+        "4405571478691884274202489443332498594710620522679849276052400952421558364239",
+        "11145823951912024380821704836389384033897335994942354146875941672098312934919",
+        "3912118985251932580529348618140951912384663919113383126509043173485933715403",
+        "2501997477381648492950318384533644783248002172679259592360114615426357826485",
+        "17644223175373572161628741746184527255986701036450183260061919219078711014796",
+        "20754593568499833911713580955829387220675692785705504569098243954247849849998",
+        "18066628912502899079801452822270241930577129659482699492972461519350830934730",
+        "9371437428152587665022432710227791158957046885081515226788797996985945852729",
+        "11024320194622053026073925643240819938835826562165012957544401105753558973509",
+        "11577357303497852714019981607780514004592408971661808469757131999060556408045",
+        "4090210303772907110685496782164216071426405360120133659500437437035314197624",
+        "19420097145397732748866686102281244485666684280609990451218101684835491098050",
+        "10768619858716722400024039278098945548612372597621875008722650842980106283937",
+        "10436907976685077206294287498031393321413710675073010295764707436042081010158",
+        "8928196585417620502141337204366090755140387227532055380831412164535429575065",
+        "10512343732072790362765752426702355177377359322584026397882806249343139757893",
+        "12973056414682468796647671878800576187904741693408535957513566087121731330044",
+        "20010200500354434303426710634894990289324949372750359476769711534927716768308",
+        "1069127076439007997418379357873777142019249745012591338916206051791809849708",
+        "20988189502199016031584564729699238703494683014194572371152171256084509252614",
+        "18226860480153879046062123190355743164266320202272556647354703507234590977502",
+        "6441573260204227540804756392972561581138742412575003767623065635507946699531",
+        "14280432873784487430353943202013585183478821345897389125785355003852412296426",
+        "12793402207625979149281036175603183551544844063315539403375560143622836184249",
+        "1761314100012910163355364640892676848271037958674929011674953971240823694381",
+    ];
+
+    fn dec(s: &str) -> Fr {
+        Fr::from_str(s).unwrap()
+    }
+
+    // message made of one 31 byte chunk per value
+    fn chunks(values: &[u8]) -> Vec<u8> {
+        let mut msg = vec![0u8; 31 * values.len()];
+        for (i, v) in values.iter().enumerate() {
+            msg[31 * i + 30] = *v;
+        }
+        msg
+    }
+
+    #[test]
+    fn test_hash_bytes() {
+        let poseidon = Poseidon::new();
+
+        let h =
+            |values: &[u8], frame_size| poseidon.hash_bytes_x(&chunks(values), frame_size).unwrap();
+        assert_eq!(h(&[1, 2], 5), dec(GO_1_2_T5));
+        assert_eq!(h(&[1, 2], 6), dec(GO_1_2_T6));
+        assert_eq!(h(&[3, 4], 5), dec(GO_3_4_T5));
+        assert_eq!(h(&[3, 4], 6), dec(GO_3_4_T6));
+        let one_to_nine: Vec<u8> = (1..=9).collect();
+        assert_eq!(h(&one_to_nine, 14), dec(GO_1_9_T14));
+        assert_eq!(
+            poseidon.hash_bytes(&chunks(&one_to_nine)).unwrap(),
+            dec(GO_1_9_T16)
+        );
+
+        // a short message is a single chunk, padded with zeros on the right
+        let h = poseidon.hash_bytes_x(&[0xde, 0xad, 0xbe, 0xef], 2).unwrap();
+        let mut chunk = [0u8; 31];
+        chunk[..4].copy_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(
+            h,
+            poseidon
+                .hash(vec![chunk_to_fr(&chunk), Fr::zero()])
+                .unwrap()
+        );
+
+        // full frames chain the previous hash in the first element
+        let msg = [1u8; 31 * 3];
+        let c = chunk_to_fr(&[1u8; 31]);
+        let h0 = poseidon.hash(vec![c, c]).unwrap();
+        assert_eq!(
+            poseidon.hash_bytes_x(&msg, 2).unwrap(),
+            poseidon.hash(vec![h0, c]).unwrap()
+        );
+
+        // several frames, with a partial last chunk
+        let msg: Vec<u8> = (0..100).collect();
+        assert_eq!(
+            poseidon.hash_bytes_x(&msg, 2).unwrap(),
+            dec(LP_BYTES_100_F2)
+        );
+        assert_eq!(
+            poseidon.hash_bytes_x(&msg, 3).unwrap(),
+            dec(LP_BYTES_100_F3)
+        );
+
+        // Go returns a nil hash here
+        poseidon.hash_bytes(&[]).expect_err("Empty input");
+        poseidon
+            .hash_bytes_x(&msg, 1)
+            .expect_err("Wrong frame size");
+        poseidon
+            .hash_bytes_x(&msg, 17)
+            .expect_err("Wrong frame size");
+    }
+
+    #[test]
+    fn test_sponge_hash() {
+        let poseidon = Poseidon::new();
+        let inp: Vec<Fr> = (1..=33).map(Fr::from_u64).collect();
+
+        assert_eq!(poseidon.sponge_hash(&inp[..2], 5).unwrap(), dec(GO_1_2_T5));
+        assert_eq!(poseidon.sponge_hash(&inp[..2], 6).unwrap(), dec(GO_1_2_T6));
+        assert_eq!(poseidon.sponge_hash(&inp[2..4], 5).unwrap(), dec(GO_3_4_T5));
+        assert_eq!(
+            poseidon.sponge_hash(&inp[..9], 14).unwrap(),
+            dec(GO_1_9_T14)
+        );
+        assert_eq!(
+            poseidon.sponge_hash(&inp[..14], 14).unwrap(),
+            dec(GO_1_14_T14)
+        );
+        assert_eq!(
+            poseidon.sponge_hash(&inp[..9], 16).unwrap(),
+            dec(GO_1_9_T16)
+        );
+        assert_eq!(
+            poseidon.sponge_hash(&inp[..16], 16).unwrap(),
+            dec(GO_1_16_T16)
+        );
+
+        // the following frames start with the previous hash
+        let h0 = dec(GO_1_16_T16);
+        let mut frame = vec![h0, inp[16]];
+        frame.resize(16, Fr::zero());
+        let h1 = poseidon.hash(frame).unwrap();
+        assert_eq!(poseidon.sponge_hash(&inp[..17], 16).unwrap(), h1);
+        let mut frame = vec![h0];
+        frame.extend_from_slice(&inp[16..31]);
+        let h1 = poseidon.hash(frame).unwrap();
+        let mut frame = vec![h1, inp[31], inp[32]];
+        frame.resize(16, Fr::zero());
+        assert_eq!(
+            poseidon.sponge_hash(&inp, 16).unwrap(),
+            poseidon.hash(frame).unwrap()
+        );
+        let h0 = poseidon.hash(inp[..3].to_vec()).unwrap();
+        let h1 = poseidon.hash(vec![h0, inp[3], inp[4]]).unwrap();
+        assert_eq!(poseidon.sponge_hash(&inp[..5], 3).unwrap(), h1);
+        for n in 1..=5 {
+            assert_eq!(
+                poseidon.sponge_hash(&inp[..n], 3).unwrap(),
+                dec(LP_SPONGE_F3[n - 1])
+            );
+        }
+        for n in 1..=33 {
+            assert_eq!(
+                poseidon.sponge_hash(&inp[..n], 12).unwrap(),
+                dec(LP_SPONGE_F12[n - 1])
+            );
+        }
+
+        // bytes are hashed as their 31 byte chunks
+        let msg: Vec<u8> = (0..100).collect();
+        let chunks: Vec<Fr> = msg.chunks(31).map(chunk_to_fr).collect();
+        assert_eq!(
+            poseidon.hash_bytes_x(&msg, 3).unwrap(),
+            poseidon.sponge_hash(&chunks, 3).unwrap()
+        );
+
+        // Go returns a nil hash here
+        assert_eq!(
+            poseidon.sponge_hash(&[], 16),
+            Err(PoseidonError::EmptyInput)
+        );
+        assert_eq!(
+            poseidon.sponge_hash(&inp, 1),
+            Err(PoseidonError::UnsupportedFrameSize(1))
+        );
+        poseidon
+            .sponge_hash(&inp, 17)
+            .expect_err("Wrong frame size");
+    }
+}
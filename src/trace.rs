@@ -0,0 +1,173 @@
+// Traces of the permutation, with the state after each step of each round,
+// to find where another implementation diverges.
+//
+// The Display output follows the hadeshash Sage reference scripts, which
+// print states as lists of 0x prefixed 64 digit hex strings, so that both
+// outputs can be diffed side by side.
+use alloc::{vec, vec::Vec};
+use core::fmt;
+
+use crate::fr::*;
+
+use crate::convert::Hex;
+use crate::permutation;
+use crate::{Fr, Poseidon, PoseidonError};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RoundTrace {
+    // whether the sbox is applied to the whole state
+    pub full: bool,
+    pub ark: Vec<Fr>,
+    pub sbox: Vec<Fr>,
+    pub mix: Vec<Fr>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trace {
+    pub n_rounds_f: usize,
+    pub n_rounds_p: usize,
+    pub input: Vec<Fr>,
+    pub rounds: Vec<RoundTrace>,
+}
+
+impl Trace {
+    pub fn output(&self) -> &[Fr] {
+        match self.rounds.last() {
+            Some(r) => &r.mix,
+            None => &self.input,
+        }
+    }
+
+    // the value returned by Poseidon::hash
+    pub fn hash(&self) -> Fr {
+        self.output()[0]
+    }
+}
+
+struct State<'a>(&'a [Fr]);
+
+impl fmt::Display for State<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[")?;
+        for (i, e) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "'{}'", Hex(*e))?;
+        }
+        write!(f, "]")
+    }
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "t = {}, R_F = {}, R_P = {}",
+            self.input.len(),
+            self.n_rounds_f,
+            self.n_rounds_p
+        )?;
+        writeln!(f, "Input:")?;
+        writeln!(f, "{}", State(&self.input))?;
+        for (i, r) in self.rounds.iter().enumerate() {
+            let kind = if r.full { "full" } else { "partial" };
+            writeln!(f, "Round {} ({}):", i, kind)?;
+            writeln!(f, "After ark: {}", State(&r.ark))?;
+            writeln!(f, "After sbox: {}", State(&r.sbox))?;
+            writeln!(f, "After mix: {}", State(&r.mix))?;
+        }
+        writeln!(f, "Output:")?;
+        writeln!(f, "{}", State(self.output()))
+    }
+}
+
+impl Poseidon {
+    // traces the permutation of width state.len() applied to state
+    pub fn permutation_trace(&self, state: &[Fr]) -> Result<Trace, PoseidonError> {
+        let t = state.len();
+        if t < 2 || t > self.constants.n_rounds_p.len() + 1 {
+            return Err(PoseidonError::UnsupportedWidth(t));
+        }
+        let n_rounds_f = self.constants.n_rounds_f;
+        let n_rounds_p = self.constants.n_rounds_p[t - 2];
+        let c = &self.constants.c[t - 2];
+        let m = &self.constants.m[t - 2];
+
+        let mut s = state.to_vec();
+        let mut rounds = Vec::with_capacity(n_rounds_f + n_rounds_p);
+        for i in 0..(n_rounds_f + n_rounds_p) {
+            self.ark(&mut s, c, i * t);
+            let ark = s.clone();
+            self.sbox(n_rounds_f, n_rounds_p, &mut s, i);
+            let sbox = s.clone();
+            let mix = self.mix(&s, m);
+            s.copy_from_slice(&mix);
+            rounds.push(RoundTrace {
+                full: permutation::is_full_round(i, n_rounds_f, n_rounds_p),
+                ark,
+                sbox,
+                mix,
+            });
+        }
+        Ok(Trace {
+            n_rounds_f,
+            n_rounds_p,
+            input: state.to_vec(),
+            rounds,
+        })
+    }
+
+    // traces the permutation used by hash(inp)
+    pub fn hash_trace(&self, inp: &[Fr]) -> Result<Trace, PoseidonError> {
+        self.check_inputs_len(inp.len())?;
+        let mut state = vec![Fr::zero(); inp.len() + 1];
+        state[1..].copy_from_slice(inp);
+        self.permutation_trace(&state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace() {
+        let poseidon = Poseidon::new();
+        let inp = vec![Fr::from_u64(1), Fr::from_u64(2)];
+        let trace = poseidon.hash_trace(&inp).unwrap();
+        assert_eq!(trace.hash(), poseidon.hash(inp.clone()).unwrap());
+        assert_eq!(trace.rounds.len(), 8 + 57);
+        assert!(trace.rounds[3].full && !trace.rounds[4].full && trace.rounds[61].full);
+        // partial rounds only apply the sbox to the first element
+        assert_eq!(trace.rounds[4].ark[1..], trace.rounds[4].sbox[1..]);
+        assert_ne!(trace.rounds[4].ark[0], trace.rounds[4].sbox[0]);
+
+        let mut state = [Fr::zero(), inp[0], inp[1]];
+        poseidon.permutation(&mut state).unwrap();
+        assert_eq!(trace.output(), &state[..]);
+
+        let s = trace.to_string();
+        let lines: Vec<&str> = s.lines().collect();
+        assert_eq!(lines.len(), 3 + 4 * 65 + 2);
+        assert_eq!(lines[0], "t = 3, R_F = 8, R_P = 57");
+        assert_eq!(
+            lines[2],
+            "['0x0000000000000000000000000000000000000000000000000000000000000000', \
+             '0x0000000000000000000000000000000000000000000000000000000000000001', \
+             '0x0000000000000000000000000000000000000000000000000000000000000002']"
+        );
+        assert!(lines[lines.len() - 1].starts_with(
+            "['0x115cc0f5e7d690413df64c6b9662e9cf2a3617f2743245519e19607a4417189a', "
+        ));
+
+        assert_eq!(
+            poseidon.hash_trace(&[]).err(),
+            Some(PoseidonError::EmptyInput)
+        );
+        assert_eq!(
+            poseidon.permutation_trace(&[Fr::zero()]).err(),
+            Some(PoseidonError::UnsupportedWidth(1))
+        );
+    }
+}
@@ -0,0 +1,145 @@
+// wasm-bindgen bindings. Field elements can be given as decimal or 0x
+// prefixed hex strings, bigints, safe integers, or 32 byte little-endian
+// Uint8Arrays, and hashes are returned as decimal strings, as circomlibjs'
+// F.toString does.
+use js_sys::{Array, BigInt, Uint8Array};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::convert::Dec;
+use crate::{Fr, Poseidon, PoseidonError};
+
+thread_local! {
+    static POSEIDON: Poseidon = Poseidon::new();
+}
+
+fn js_error(e: PoseidonError) -> JsError {
+    JsError::new(&e.to_string())
+}
+
+fn parse_str(s: &str) -> Result<Fr, PoseidonError> {
+    let r = if s.starts_with("0x") {
+        Fr::parse_hex(s)
+    } else {
+        Fr::parse_dec(s)
+    };
+    Ok(r?)
+}
+
+fn fr_from_js(v: &JsValue) -> Result<Fr, PoseidonError> {
+    if let Some(s) = v.as_string() {
+        return parse_str(&s);
+    }
+    if v.is_bigint() {
+        let s = BigInt::from(v.clone())
+            .to_string(10)
+            .map_err(|_| PoseidonError::InvalidEncoding("Invalid bigint"))?;
+        return parse_str(&String::from(s));
+    }
+    if let Some(b) = v.dyn_ref::<Uint8Array>() {
+        return Ok(Fr::from_slice_le(&b.to_vec())?);
+    }
+    match v.as_f64() {
+        Some(n) if n >= 0.0 && n.fract() == 0.0 && n <= 9007199254740991.0 => {
+            Ok(Fr::from_u64(n as u64))
+        }
+        _ => Err(PoseidonError::InvalidEncoding(
+            "Expected a string, bigint, integer or Uint8Array",
+        )),
+    }
+}
+
+fn frs_from_js(inputs: &Array) -> Result<Vec<Fr>, PoseidonError> {
+    inputs.iter().map(|v| fr_from_js(&v)).collect()
+}
+
+#[wasm_bindgen]
+pub fn hash(inputs: Array) -> Result<String, JsError> {
+    let inp = frs_from_js(&inputs).map_err(js_error)?;
+    let h = POSEIDON.with(|p| p.hash(inp)).map_err(js_error)?;
+    Ok(Dec(h).to_string())
+}
+
+#[wasm_bindgen(js_name = hashBytes)]
+pub fn hash_bytes(msg: &[u8]) -> Result<String, JsError> {
+    let h = POSEIDON.with(|p| p.hash_bytes(msg)).map_err(js_error)?;
+    Ok(Dec(h).to_string())
+}
+
+#[wasm_bindgen(js_name = hashBytesX)]
+pub fn hash_bytes_x(msg: &[u8], frame_size: usize) -> Result<String, JsError> {
+    let h = POSEIDON
+        .with(|p| p.hash_bytes_x(msg, frame_size))
+        .map_err(js_error)?;
+    Ok(Dec(h).to_string())
+}
+
+#[wasm_bindgen(js_name = spongeHash)]
+pub fn sponge_hash(inputs: Array, frame_size: usize) -> Result<String, JsError> {
+    let inp = frs_from_js(&inputs).map_err(js_error)?;
+    let h = POSEIDON
+        .with(|p| p.sponge_hash(&inp, frame_size))
+        .map_err(js_error)?;
+    Ok(Dec(h).to_string())
+}
+
+// run with `wasm-pack test --node -- --features wasm`
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    // circomlibjs: F.toString(poseidon([1, 2]))
+    const HASH_1_2: &str =
+        "7853200120776062878684798364095072458815029376092732009249414926327459813530";
+
+    // light-poseidon 0.2, chaining the frames as SpongeHashX, see sponge.rs
+    const SPONGE_1_5_F3: &str =
+        "2471372894682600937379875366137951766263546689296527096431777319482417487166";
+    const SPONGE_1_33_F12: &str =
+        "1761314100012910163355364640892676848271037958674929011674953971240823694381";
+
+    fn input(v: JsValue) -> Array {
+        let a = Array::new();
+        a.push(&v);
+        a.push(&JsValue::from_str("2"));
+        a
+    }
+
+    #[wasm_bindgen_test]
+    fn test_hash() {
+        assert_eq!(hash(input(JsValue::from_str("1"))).unwrap(), HASH_1_2);
+        assert_eq!(hash(input(JsValue::from_str("0x01"))).unwrap(), HASH_1_2);
+        assert_eq!(hash(input(JsValue::from_f64(1.0))).unwrap(), HASH_1_2);
+        assert_eq!(hash(input(BigInt::from(1u64).into())).unwrap(), HASH_1_2);
+        let mut one = [0u8; 32];
+        one[0] = 1;
+        let bytes = Uint8Array::from(&one[..]);
+        assert_eq!(hash(input(bytes.into())).unwrap(), HASH_1_2);
+
+        assert!(hash(Array::new()).is_err());
+        assert!(hash(input(JsValue::from_f64(1.5))).is_err());
+        assert!(hash(input(JsValue::from_str("01"))).is_err());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_sponge() {
+        let msg = b"Lorem ipsum dolor sit amet";
+        let h = hash_bytes(msg).unwrap();
+        assert_eq!(hash_bytes_x(msg, 16).unwrap(), h);
+        assert_ne!(hash_bytes_x(msg, 2).unwrap(), h);
+
+        let a = Array::new();
+        for i in 1..=33 {
+            a.push(&JsValue::from_f64(i as f64));
+        }
+        assert_eq!(sponge_hash(a.clone(), 12).unwrap(), SPONGE_1_33_F12);
+        // checked against go-iden3-crypto by the go job of CI
+        assert_eq!(
+            sponge_hash(a.clone(), 16).unwrap(),
+            "14100754985345369187053942966124810217803624427243640962979300983969861343646"
+        );
+        assert_eq!(sponge_hash(a.slice(0, 5), 3).unwrap(), SPONGE_1_5_F3);
+        assert!(sponge_hash(Array::new(), 16).is_err());
+    }
+}
@@ -0,0 +1,5 @@
+module github.com/arnaucube/poseidon-rs/tests/go
+
+go 1.21
+
+require github.com/iden3/go-iden3-crypto v0.0.16
@@ -0,0 +1,57 @@
+// Prints known-answer vectors computed with go-iden3-crypto, one per line:
+// the expected output followed by the arguments of the poseidon CLI that
+// should print it. The go job of CI runs the CLI on each line and compares.
+package main
+
+import (
+	"encoding/hex"
+	"fmt"
+	"math/big"
+	"os"
+	"strings"
+
+	"github.com/iden3/go-iden3-crypto/poseidon"
+)
+
+func check(err error) {
+	if err != nil {
+		fmt.Fprintln(os.Stderr, err)
+		os.Exit(1)
+	}
+}
+
+func main() {
+	// 1..=n, for frames of 2 to 16 elements
+	inputs := []*big.Int{}
+	args := []string{}
+	for n := 1; n <= 33; n++ {
+		inputs = append(inputs, big.NewInt(int64(n)))
+		args = append(args, fmt.Sprint(n))
+		arg := strings.Join(args, " ")
+		h, err := poseidon.SpongeHash(inputs)
+		check(err)
+		fmt.Println(h, "sponge", arg)
+		for frameSize := 2; frameSize <= 16; frameSize++ {
+			h, err := poseidon.SpongeHashX(inputs, frameSize)
+			check(err)
+			fmt.Println(h, "sponge --frame-size", frameSize, arg)
+		}
+	}
+
+	// messages of one, several and a partial last chunk of 31 bytes
+	for _, n := range []int{1, 31, 32, 100, 500} {
+		msg := make([]byte, n)
+		for i := range msg {
+			msg[i] = byte(i)
+		}
+		arg := "0x" + hex.EncodeToString(msg)
+		h, err := poseidon.HashBytes(msg)
+		check(err)
+		fmt.Println(h, "hash-bytes --hex-input", arg)
+		for frameSize := 2; frameSize <= 16; frameSize++ {
+			h, err := poseidon.HashBytesX(msg, frameSize)
+			check(err)
+			fmt.Println(h, "hash-bytes --hex-input --frame-size", frameSize, arg)
+		}
+	}
+}
@@ -0,0 +1,3 @@
+// cdylib wrapper of the wasm-bindgen bindings of poseidon-rs (src/wasm.rs),
+// so that poseidon-rs itself stays an rlib.
+pub use poseidon_rs::wasm::*;